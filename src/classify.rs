@@ -0,0 +1,221 @@
+use std::fmt;
+
+use fs::Entry;
+use parse;
+use parse::episode::EpisodeInfo;
+use tree::{Node, Tree};
+
+/// Kind of media found in a subtree, see `patterns.md` for the layouts recognised.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Kind {
+    Movie,
+    Episode,
+    Season,
+    Series,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Kind::Movie => "movie",
+            Kind::Episode => "single episode",
+            Kind::Season => "season pack",
+            Kind::Series => "full series",
+        })
+    }
+}
+
+fn season_folder(tree: &Tree<Entry>, node: Node) -> Option<i32> {
+    let entry = tree.data(node);
+    if entry.is_dir() {
+        entry
+            .file_name()
+            .and_then(|name| parse::episode::parse_season_folder(&name.to_string_lossy()))
+    } else {
+        None
+    }
+}
+
+/// Use a folder's name as the show's name, `Firefly (2002)` becomes `firefly` and 2002.
+fn show_folder(tree: &Tree<Entry>, node: Node) -> Option<(String, Option<i32>)> {
+    let entry = tree.data(node);
+    entry.file_name().map(|name| {
+        let (show, year) = parse::movie::parse_movie(&name.to_string_lossy());
+        // Season pack folders are often named 'Show.S02.1080p', drop the season from the name.
+        let show = show.split(' ')
+            .take_while(|word| parse::episode::parse_season_folder(word).is_none())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (show, year)
+    })
+}
+
+/// Whether the folder of a video only holds that episode, such as `02` in
+/// `Show/Season 2/02/02.mkv` or `Show.S02E03` in `Show/Show.S02E03/03.mkv`.
+fn episode_folder(tree: &Tree<Entry>, video: Node, folder: Node) -> bool {
+    let name = match tree.data(folder).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return false,
+    };
+    let in_season = tree.parent(folder).and_then(|p| season_folder(tree, p)).is_some();
+    !tree.siblings(video).any(|n| tree.data(n).is_video()) && season_folder(tree, folder).is_none()
+        && (in_season || parse::episode::parse_episode(&name).is_some())
+}
+
+/// Parse a video file as an episode, using the names of the folders containing it as context when
+/// the filename is ambiguous.
+///
+/// In `Show/Season 2/02.mkv`, the season comes from `Season 2` and the show's name from `Show`. In
+/// `Show/S02E03.mkv`, the show's name comes from `Show`. Episodes in their own folder, as in
+/// `Show/Season 2/02/02.mkv`, use the folders above it. Returns `None` if the file does not look
+/// like an episode.
+pub fn episode_info(tree: &Tree<Entry>, node: Node) -> Option<EpisodeInfo> {
+    let entry = tree.data(node);
+    let stem = entry.stem()?;
+    let parent = tree.parent(node);
+    let folder = match parent {
+        Some(p) if episode_folder(tree, node, p) => tree.parent(p).or(parent),
+        _ => parent,
+    };
+    let season = folder.and_then(|f| season_folder(tree, f));
+
+    let mut info = match season {
+        Some(season) => parse::episode::parse_episode_in_season(stem, season)?,
+        None => parse::episode::parse_episode(stem)?,
+    };
+
+    if info.show.is_empty() {
+        let show_node = match season {
+            Some(_) => folder.and_then(|f| tree.parent(f)),
+            None => folder,
+        };
+        if let Some((show, year)) = show_node.and_then(|n| show_folder(tree, n)) {
+            info.show = show;
+            info.year = info.year.or(year);
+        }
+    }
+
+    Some(info)
+}
+
+/// Classify the subtree starting at the given node.
+///
+/// Returns `None` if the subtree contains no video or if it contains a mix of movies and episodes,
+/// or episodes from different shows. Such subtrees should have their children classified instead,
+/// see `classify_all`.
+pub fn classify(tree: &Tree<Entry>, node: Node) -> Option<Kind> {
+    let entry = tree.data(node);
+    if entry.is_file() {
+        if !entry.is_video() {
+            return None;
+        }
+        return Some(match episode_info(tree, node) {
+            Some(_) => Kind::Episode,
+            None => Kind::Movie,
+        });
+    }
+
+    let mut videos = 0;
+    let mut episodes: Vec<EpisodeInfo> = vec![];
+    for child in tree.recursive_iter(node) {
+        let entry = tree.data(child);
+        if entry.is_file() && entry.is_video() {
+            videos += 1;
+            if let Some(info) = episode_info(tree, child) {
+                episodes.push(info);
+            }
+        }
+    }
+
+    if videos == 0 {
+        return None;
+    }
+    if episodes.is_empty() {
+        return if videos == 1 { Some(Kind::Movie) } else { None };
+    }
+    if episodes.len() != videos || episodes.iter().any(|e| e.show != episodes[0].show) {
+        return None;
+    }
+
+    let has_season_folders = tree.children(node)
+        .any(|child| season_folder(tree, child).is_some());
+    let multiple_seasons = episodes.iter().any(|e| e.season != episodes[0].season);
+
+    if has_season_folders || multiple_seasons {
+        Some(Kind::Series)
+    } else if episodes.len() == 1 {
+        Some(Kind::Episode)
+    } else {
+        Some(Kind::Season)
+    }
+}
+
+/// Classify every subtree starting from the given node. The largest subtrees that can be
+/// classified are returned, subtrees that cannot be classified have their children classified.
+pub fn classify_all(tree: &Tree<Entry>, node: Node) -> Vec<(Node, Kind)> {
+    let mut kinds = vec![];
+    let mut nodes = vec![node];
+    while let Some(node) = nodes.pop() {
+        match classify(tree, node) {
+            Some(kind) => kinds.push((node, kind)),
+            None => nodes.extend(tree.children(node)),
+        }
+    }
+    kinds
+}
+
+#[cfg(test)]
+fn classify_path(path: &str) -> Option<Kind> {
+    let (tree, root) = ::fs::walk(path).unwrap();
+    classify(&tree, root)
+}
+
+#[test]
+fn test_classify() {
+    assert_eq!(classify_path("testdata/The.Matrix.1999"), Some(Kind::Movie));
+    assert_eq!(
+        classify_path("testdata/tv/Breaking.Bad.S05E14"),
+        Some(Kind::Episode)
+    );
+    assert_eq!(
+        classify_path("testdata/tv/The.Wire.S03.720p"),
+        Some(Kind::Season)
+    );
+    assert_eq!(classify_path("testdata/tv/Cowboy Bebop"), Some(Kind::Series));
+    assert_eq!(classify_path("testdata/tv/Firefly (2002)"), Some(Kind::Series));
+    assert_eq!(classify_path("testdata/nested/Lost (2004)"), Some(Kind::Series));
+    assert_eq!(
+        classify_path("testdata/nested/Lost (2004)/Season 1"),
+        Some(Kind::Season)
+    );
+    assert_eq!(classify_path("testdata/tv"), None);
+    assert_eq!(classify_path("testdata/subs"), None);
+}
+
+#[test]
+fn test_episode_info_context() {
+    let (tree, root) = ::fs::walk("testdata/tv/Firefly (2002)").unwrap();
+    let node = tree.recursive_iter(root)
+        .find(|&n| tree.data(n).stem() == Some("02 - The Train Job"))
+        .unwrap();
+    let info = episode_info(&tree, node).unwrap();
+    assert_eq!(info.show, "firefly");
+    assert_eq!(info.year, Some(2002));
+    assert_eq!(info.season, Some(1));
+    assert_eq!(info.episodes, vec![2]);
+    assert_eq!(info.title, Some("the train job".into()));
+}
+
+#[test]
+fn test_episode_info_nested() {
+    let (tree, root) = ::fs::walk("testdata/nested").unwrap();
+    let node = tree.recursive_iter(root)
+        .find(|&n| tree.data(n).is_file() && tree.data(n).stem() == Some("01 - Man of Science"))
+        .unwrap();
+    let info = episode_info(&tree, node).unwrap();
+    assert_eq!(info.show, "lost");
+    assert_eq!(info.year, Some(2004));
+    assert_eq!(info.season, Some(2));
+    assert_eq!(info.episodes, vec![1]);
+    assert_eq!(info.title, Some("man of science".into()));
+}
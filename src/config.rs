@@ -1,7 +1,372 @@
-#[derive(Deserialize, Serialize)]
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use error;
+use fs::TransferMode;
+use subtitle;
+use template::{self, Template};
+
+/// Configuration of the application, read from a TOML file.
+///
+/// The file is looked for in this order: the `--config` argument, the `MERO_CONFIG` environment
+/// variable, `$XDG_CONFIG_HOME/merovingian/config.toml` and `~/.config/merovingian/config.toml`.
+/// Some settings can also be overridden by environment variables, see `apply_env`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
-    pub movies_path: Option<PathBuf>,
-    pub tv_path: Option<PathBuf>,
+    /// Path of the database file.
+    pub database: Option<PathBuf>,
+    /// Directory of the TMDB response cache.
+    pub cache: Option<PathBuf>,
+    /// TMDB v3 API key or v4 read access token. Can be overridden by `MERO_API_KEY`.
+    pub api_key: Option<String>,
+    /// Language of the metadata, such as `en-US` or `fr-CA`.
+    pub language: Option<String>,
+    /// Country of the release dates and certifications, such as `US` or `CA`. Defaults to the
+    /// country of the language.
+    pub region: Option<String>,
+    /// Title used to name the files, the title in the configured language or the original title.
+    pub title: Title,
+    /// How files are transferred to the libraries.
+    pub transfer: TransferMode,
+    /// Glob patterns of files and directories skipped during imports, such as `sample*`.
+    pub ignore: Vec<String>,
+    pub movies: Library,
+    pub tv: Library,
+    pub images: Images,
+    pub nfo: Nfo,
+    pub subtitles: Subtitles,
+
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    overridden: Overridden,
+}
+
+/// Values from the file of the settings overridden by environment variables, saved instead of the
+/// overrides.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Overridden {
+    database: Option<Option<PathBuf>>,
+    movies_path: Option<Option<PathBuf>>,
+    tv_path: Option<Option<PathBuf>>,
+    language: Option<Option<String>>,
+    region: Option<Option<String>>,
+    transfer: Option<TransferMode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Library {
+    /// Root directory of the library.
+    pub path: Option<PathBuf>,
+    /// Naming template of the files in the library, see `template::Template`.
+    pub template: Option<String>,
+    /// What happens when a movie or an episode already in the library is imported again.
+    pub duplicates: Duplicates,
+    /// Directory where copies replaced by better ones are moved, `.trash` in the library by
+    /// default.
+    pub trash: Option<PathBuf>,
+}
+
+impl Library {
+    pub fn trash_path(&self) -> Option<PathBuf> {
+        self.trash
+            .clone()
+            .or_else(|| self.path.as_ref().map(|path| path.join(".trash")))
+    }
+}
+
+/// Policy for the copies of a movie or an episode, see `quality::Quality` for how copies are
+/// compared.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Duplicates {
+    /// Keep every copy, numbered with the `version` template field.
+    All,
+    /// Keep only the best copy. Copies that are not better are skipped, better copies replace
+    /// the ones in the library.
+    Best,
+    /// Better copies replace the ones in the library, other copies are kept alongside them.
+    Upgrade,
+}
+
+impl Default for Duplicates {
+    fn default() -> Duplicates {
+        Duplicates::All
+    }
 }
 
-impl Config {}
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Title {
+    Localized,
+    Original,
+}
+
+impl Default for Title {
+    fn default() -> Title {
+        Title::Localized
+    }
+}
+
+/// Posters and backdrops downloaded next to the movies.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Images {
+    pub download: bool,
+    /// Size of the posters, such as `w342`, `w780` or `original`.
+    pub poster_size: String,
+    /// Size of the backdrops, such as `w780`, `w1280` or `original`.
+    pub backdrop_size: String,
+}
+
+impl Default for Images {
+    fn default() -> Images {
+        Images {
+            download: true,
+            poster_size: "w780".into(),
+            backdrop_size: "w1280".into(),
+        }
+    }
+}
+
+/// Kodi NFO files written next to the movies, also read by Jellyfin and Emby.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Nfo {
+    pub write: bool,
+}
+
+impl Default for Nfo {
+    fn default() -> Nfo {
+        Nfo { write: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Subtitles {
+    /// Convert the subtitles to this format during imports, `srt` or `vtt`, for players that only
+    /// support one of them. Other formats are kept as is.
+    pub convert: Option<subtitle::Format>,
+}
+
+/// Default location of the configuration file.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    config_home.map(|dir| dir.join("merovingian").join("config.toml"))
+}
+
+/// Default directory of the TMDB response cache.
+pub fn default_cache_path() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")));
+    cache_home.map(|dir| dir.join("merovingian"))
+}
+
+impl Config {
+    /// Load the configuration file, using the path given or the default locations. A missing file
+    /// gives the default configuration, which will be saved at that path.
+    pub fn load(path: Option<PathBuf>) -> Result<Config, error::Error> {
+        let path = path.or_else(|| env::var_os("MERO_CONFIG").map(PathBuf::from))
+            .or_else(default_path)
+            .unwrap_or_else(|| PathBuf::from("config.toml"));
+
+        let mut config = if path.exists() {
+            let mut text = String::new();
+            File::open(&path)?.read_to_string(&mut text)?;
+            toml::from_str(&text)?
+        } else {
+            Config::default()
+        };
+
+        config.path = path;
+        config.apply_env();
+        Ok(config)
+    }
+
+    /// Override the settings with the environment variables `MERO_DATABASE`, `MERO_MOVIES_PATH`,
+    /// `MERO_TV_PATH`, `MERO_LANGUAGE`, `MERO_REGION` and `MERO_TRANSFER`. The overrides are not
+    /// saved.
+    fn apply_env(&mut self) {
+        let overridden = &mut self.overridden;
+        if let Some(database) = env::var_os("MERO_DATABASE") {
+            overridden.database = Some(mem::replace(&mut self.database, Some(database.into())));
+        }
+        if let Some(path) = env::var_os("MERO_MOVIES_PATH") {
+            overridden.movies_path = Some(mem::replace(&mut self.movies.path, Some(path.into())));
+        }
+        if let Some(path) = env::var_os("MERO_TV_PATH") {
+            overridden.tv_path = Some(mem::replace(&mut self.tv.path, Some(path.into())));
+        }
+        if let Ok(language) = env::var("MERO_LANGUAGE") {
+            overridden.language = Some(mem::replace(&mut self.language, Some(language)));
+        }
+        if let Ok(region) = env::var("MERO_REGION") {
+            overridden.region = Some(mem::replace(&mut self.region, Some(region)));
+        }
+        if let Ok(transfer) = env::var("MERO_TRANSFER") {
+            match toml::Value::String(transfer.clone()).try_into() {
+                Ok(mode) => overridden.transfer = Some(mem::replace(&mut self.transfer, mode)),
+                Err(_) => println!("Ignoring invalid MERO_TRANSFER value {}.", transfer),
+            }
+        }
+    }
+
+    /// Save the configuration to the file it was loaded from, with the values of the file for the
+    /// settings overridden by environment variables.
+    pub fn save(&self) -> Result<(), error::Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(&self.without_env())?;
+        File::create(&self.path)?.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// The configuration with the settings overridden by environment variables set back to their
+    /// values in the file.
+    fn without_env(&self) -> Config {
+        let mut config = self.clone();
+        let overridden = &self.overridden;
+        if let Some(ref database) = overridden.database {
+            config.database = database.clone();
+        }
+        if let Some(ref path) = overridden.movies_path {
+            config.movies.path = path.clone();
+        }
+        if let Some(ref path) = overridden.tv_path {
+            config.tv.path = path.clone();
+        }
+        if let Some(ref language) = overridden.language {
+            config.language = language.clone();
+        }
+        if let Some(ref region) = overridden.region {
+            config.region = region.clone();
+        }
+        if let Some(transfer) = overridden.transfer {
+            config.transfer = transfer;
+        }
+        config
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn database_path(&self) -> &Path {
+        self.database
+            .as_ref()
+            .map(PathBuf::as_path)
+            .unwrap_or(Path::new("database.json"))
+    }
+
+    pub fn cache_path(&self) -> PathBuf {
+        self.cache
+            .clone()
+            .or_else(default_cache_path)
+            .unwrap_or_else(|| PathBuf::from("cache"))
+    }
+
+    /// Country used for certifications, the region, the country of the language or `US`.
+    pub fn country(&self) -> &str {
+        self.region
+            .as_ref()
+            .map(String::as_str)
+            .or_else(|| self.language.as_ref().and_then(|lang| lang.split('-').nth(1)))
+            .unwrap_or("US")
+    }
+
+    pub fn movies_path(&self) -> &Path {
+        self.movies
+            .path
+            .as_ref()
+            .expect("the movies library is not configured")
+    }
+
+    pub fn tv_path(&self) -> &Path {
+        self.tv.path.as_ref().expect("the tv library is not configured")
+    }
+
+    /// Naming template of the movie library, see `template::Template::movie`.
+    pub fn movie_template(&self) -> Result<Template, template::Error> {
+        Template::movie(
+            self.movies
+                .template
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or(template::DEFAULT_MOVIE_TEMPLATE),
+        )
+    }
+
+    /// Naming template of the tv library, see `template::Template::episode`.
+    pub fn episode_template(&self) -> Result<Template, template::Error> {
+        Template::episode(
+            self.tv
+                .template
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or(template::DEFAULT_EPISODE_TEMPLATE),
+        )
+    }
+}
+
+#[test]
+fn test_parse_config() {
+    let config: Config = toml::from_str(
+        r#"
+        api_key = "abc"
+        language = "fr-CA"
+        title = "original"
+        transfer = "move"
+        ignore = ["sample*", "*.part"]
+
+        [movies]
+        path = "/media/movies"
+        template = "{title} ({year})/{title}{version}.{ext}"
+        duplicates = "upgrade"
+        "#,
+    ).unwrap();
+    assert_eq!(config.api_key, Some("abc".into()));
+    assert_eq!(config.transfer, TransferMode::Move);
+    assert_eq!(config.country(), "CA");
+    assert_eq!(config.title, Title::Original);
+    assert_eq!(config.ignore, vec!["sample*", "*.part"]);
+    assert_eq!(config.movies_path(), Path::new("/media/movies"));
+    assert_eq!(config.movies.duplicates, Duplicates::Upgrade);
+    assert_eq!(config.movies.trash_path(), Some(PathBuf::from("/media/movies/.trash")));
+    assert_eq!(config.tv.duplicates, Duplicates::All);
+    assert!(config.movie_template().is_ok());
+    assert_eq!(config.tv.path, None);
+    assert_eq!(config.database_path(), Path::new("database.json"));
+}
+
+#[test]
+fn test_env_not_saved() {
+    let mut config: Config = toml::from_str(
+        r#"
+        language = "fr-CA"
+
+        [movies]
+        path = "/media/movies"
+        "#,
+    ).unwrap();
+    config.overridden.language = Some(mem::replace(&mut config.language, Some("en-US".into())));
+    config.overridden.tv_path = Some(mem::replace(&mut config.tv.path, Some("/tmp/tv".into())));
+    config.movies.template = Some("{title}.{ext}".into());
+
+    let saved = config.without_env();
+    assert_eq!(saved.language, Some("fr-CA".into()));
+    assert_eq!(saved.tv.path, None);
+    assert_eq!(saved.movies.path, Some(PathBuf::from("/media/movies")));
+    assert_eq!(saved.movies.template, Some("{title}.{ext}".into()));
+}
@@ -0,0 +1,181 @@
+use std::io::{self, Read};
+
+use database::{Track, TrackKind};
+use super::{invalid, new_track, MediaInfo};
+
+/// The header list describes the streams, a few kilobytes. A larger one means a corrupted file.
+const MAX_HEADER_SIZE: u32 = 1024 * 1024;
+
+type FourCC = [u8; 4];
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    data[pos] as u16 | (data[pos + 1] as u16) << 8
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u16_at(data, pos) as u32 | (u16_at(data, pos + 2) as u32) << 16
+}
+
+fn fourcc(data: &[u8], pos: usize) -> FourCC {
+    [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]
+}
+
+/// The chunks in the body of a list, as ids and bodies. Lists are returned with their type as
+/// first four bytes of the body.
+fn chunks(data: &[u8]) -> io::Result<Vec<(FourCC, &[u8])>> {
+    let mut found = vec![];
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let id = fourcc(data, pos);
+        let size = u32_at(data, pos + 4) as usize;
+        let start = pos + 8;
+        if size > data.len() - start {
+            return Err(invalid("truncated AVI chunk"));
+        }
+        found.push((id, &data[start..start + size]));
+        // Chunks are padded to an even size.
+        pos = start + size + size % 2;
+    }
+    Ok(found)
+}
+
+/// Name of the audio codec of a `WAVEFORMATEX` format tag.
+fn audio_codec(tag: u16) -> String {
+    match tag {
+        0x0001 => "pcm".into(),
+        0x0055 => "mp3".into(),
+        0x00FF | 0x1610 => "aac".into(),
+        0x2000 => "ac3".into(),
+        0x2001 => "dts".into(),
+        _ => format!("0x{:04x}", tag),
+    }
+}
+
+/// Track of a stream list, from its stream header and its format.
+fn track(strl: &[u8]) -> io::Result<Option<Track>> {
+    let chunks = chunks(strl)?;
+    let strh = chunks.iter().find(|&&(id, _)| &id == b"strh").map(|&(_, body)| body);
+    let strf = chunks.iter().find(|&&(id, _)| &id == b"strf").map(|&(_, body)| body);
+    let strh = match strh {
+        Some(strh) if strh.len() >= 8 => strh,
+        _ => return Ok(None),
+    };
+    let track = match (&fourcc(strh, 0), strf) {
+        (b"vids", Some(strf)) if strf.len() >= 20 => {
+            let codec = String::from_utf8_lossy(&strf[16..20]).trim().to_lowercase();
+            let mut track = new_track(TrackKind::Video, codec, None);
+            track.width = Some(u32_at(strf, 4));
+            // Top-down bitmaps have negative heights, the lowest one is invalid.
+            track.height = (u32_at(strf, 8) as i32).checked_abs().map(|height| height as u32);
+            track
+        }
+        (b"auds", Some(strf)) if strf.len() >= 4 => {
+            let mut track = new_track(TrackKind::Audio, audio_codec(u16_at(strf, 0)), None);
+            track.channels = Some(u16_at(strf, 2) as u32);
+            track
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(track))
+}
+
+/// Read the duration and the tracks of an AVI file, from its header list at the start of the
+/// file. AVI files have no languages.
+pub fn read<R: Read>(reader: &mut R) -> io::Result<MediaInfo> {
+    let mut riff = [0u8; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[..4] != b"RIFF" || &riff[8..] != b"AVI " {
+        return Err(invalid("not an AVI file"));
+    }
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    let size = u32_at(&header, 4);
+    if &header[..4] != b"LIST" || &header[8..] != b"hdrl" || size < 4 {
+        return Err(invalid("missing AVI header list"));
+    }
+    if size > MAX_HEADER_SIZE {
+        return Err(invalid("AVI header list is too large"));
+    }
+    let mut body = vec![0; size as usize - 4];
+    reader.read_exact(&mut body)?;
+
+    let mut info = MediaInfo::default();
+    for (id, chunk) in chunks(&body)? {
+        match &id {
+            b"avih" if chunk.len() >= 20 => {
+                let frame = u32_at(chunk, 0) as f64;
+                let frames = u32_at(chunk, 16) as f64;
+                if frame > 0.0 && frames > 0.0 {
+                    info.duration = Some(frames * frame / 1_000_000.0);
+                }
+            }
+            b"LIST" if chunk.len() >= 4 && &chunk[..4] == b"strl" => {
+                info.tracks.extend(track(&chunk[4..])?);
+            }
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+fn riff_chunk(id: &[u8], body: &[u8]) -> Vec<u8> {
+    let size = body.len() as u32;
+    let mut data = id.to_vec();
+    data.extend(&[size as u8, (size >> 8) as u8, (size >> 16) as u8, (size >> 24) as u8]);
+    data.extend(body);
+    if body.len() % 2 == 1 {
+        data.push(0);
+    }
+    data
+}
+
+#[test]
+fn test_read() {
+    use std::io::Cursor;
+
+    // 40000 microseconds per frame, 25 frames per second, and 180000 frames.
+    let mut avih = vec![0; 56];
+    avih[..4].copy_from_slice(&[0x40, 0x9C, 0, 0]);
+    avih[16..20].copy_from_slice(&[0x20, 0xBF, 0x02, 0]);
+    // 720x304 XviD.
+    let mut bitmap = vec![0; 40];
+    bitmap[4..12].copy_from_slice(&[0xD0, 0x02, 0, 0, 0x30, 0x01, 0, 0]);
+    bitmap[16..20].copy_from_slice(b"XVID");
+    // Stereo MP3, with an odd size.
+    let wave = [0x55, 0, 2, 0, 0x44, 0xAC, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    let video = [
+        &b"strl"[..],
+        &riff_chunk(b"strh", &[&b"vids"[..], &b"XVID"[..], &[0; 48][..]].concat()),
+        &riff_chunk(b"strf", &bitmap),
+    ].concat();
+    let audio = [
+        &b"strl"[..],
+        &riff_chunk(b"strh", &[&b"auds"[..], &[0; 52][..]].concat()),
+        &riff_chunk(b"strf", &wave),
+    ].concat();
+    let hdrl = [
+        &b"hdrl"[..],
+        &riff_chunk(b"avih", &avih),
+        &riff_chunk(b"LIST", &video),
+        &riff_chunk(b"LIST", &audio),
+    ].concat();
+    let file = [&b"RIFF\0\0\0\0AVI "[..], &riff_chunk(b"LIST", &hdrl)].concat();
+
+    let info = read(&mut Cursor::new(file)).unwrap();
+    assert_eq!(info.duration, Some(7200.0));
+    assert_eq!(info.tracks.len(), 2);
+    assert_eq!(info.tracks[0].kind, TrackKind::Video);
+    assert_eq!(info.tracks[0].codec, "xvid");
+    assert_eq!((info.tracks[0].width, info.tracks[0].height), (Some(720), Some(304)));
+    assert_eq!(info.tracks[1].kind, TrackKind::Audio);
+    assert_eq!(info.tracks[1].codec, "mp3");
+    assert_eq!(info.tracks[1].channels, Some(2));
+
+    assert!(read(&mut Cursor::new(b"RIFF\0\0\0\0WAVEfmt ".to_vec())).is_err());
+
+    bitmap[8..12].copy_from_slice(&[0, 0, 0, 0x80]);
+    let strl = [riff_chunk(b"strh", b"vidsXVID"), riff_chunk(b"strf", &bitmap)].concat();
+    assert_eq!(track(&strl).unwrap().unwrap().height, None);
+}
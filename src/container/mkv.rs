@@ -0,0 +1,337 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use database::{Hdr, Track, TrackKind};
+use super::{invalid, language, new_track, set_transfer, MediaInfo};
+
+const EBML: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x002A_D7B1;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const CLUSTER: u32 = 0x1F43_B675;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const NAME: u32 = 0x536E;
+const LANGUAGE: u32 = 0x0022_B59C;
+const LANGUAGE_IETF: u32 = 0x0022_B59D;
+const FLAG_DEFAULT: u32 = 0x88;
+const FLAG_FORCED: u32 = 0x55AA;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const COLOUR: u32 = 0x55B0;
+const TRANSFER_CHARACTERISTICS: u32 = 0x55BA;
+const BLOCK_ADDITION_MAPPING: u32 = 0x41E4;
+const BLOCK_ADD_ID_TYPE: u32 = 0x41E7;
+const AUDIO: u32 = 0xE1;
+const CHANNELS: u32 = 0x9F;
+
+/// Block additions holding the Dolby Vision configuration, `dvcC` and `dvvC`.
+const DOLBY_VISION: [u64; 2] = [0x6476_6343, 0x6476_7643];
+/// Default scale of the timestamps, in nanoseconds.
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Sizes of the elements that are read in memory, the headers are a few kilobytes, a larger one
+/// means a corrupted file.
+const MAX_HEADER_SIZE: u64 = 1024 * 1024;
+
+/// Size of the elements whose size is unknown, such as the segments of live streams.
+const UNKNOWN_SIZE: u64 = ::std::u64::MAX;
+
+/// Read a variable size integer. The length marker is kept in ids and removed from sizes, where a
+/// value of all ones means that the size is unknown.
+fn vint<R: Read>(reader: &mut R, is_id: bool) -> io::Result<u64> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte)?;
+    let len = byte[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        return Err(invalid("invalid Matroska integer"));
+    }
+    let mask = (0xFFu16 >> len) as u8;
+    let first = if is_id { byte[0] } else { byte[0] & mask };
+    let mut value = first as u64;
+    let mut all_ones = byte[0] & mask == mask;
+    for _ in 1..len {
+        reader.read_exact(&mut byte)?;
+        all_ones = all_ones && byte[0] == 0xFF;
+        value = (value << 8) | byte[0] as u64;
+    }
+    if !is_id && all_ones {
+        Ok(UNKNOWN_SIZE)
+    } else {
+        Ok(value)
+    }
+}
+
+/// Read the id and the size of an element.
+fn header<R: Read>(reader: &mut R) -> io::Result<(u32, u64)> {
+    let id = vint(reader, true)?;
+    let size = vint(reader, false)?;
+    Ok((id as u32, size))
+}
+
+/// The child elements in the body of an element, as ids and bodies.
+fn children(data: &[u8]) -> io::Result<Vec<(u32, &[u8])>> {
+    let mut cursor = Cursor::new(data);
+    let mut found = vec![];
+    while (cursor.position() as usize) < data.len() {
+        let (id, size) = header(&mut cursor)?;
+        let start = cursor.position() as usize;
+        if size > (data.len() - start) as u64 {
+            return Err(invalid("truncated Matroska element"));
+        }
+        let end = start + size as usize;
+        found.push((id, &data[start..end]));
+        cursor.set_position(end as u64);
+    }
+    Ok(found)
+}
+
+fn uint(data: &[u8]) -> u64 {
+    data.iter().fold(0, |value, &byte| (value << 8) | byte as u64)
+}
+
+fn float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_bits(uint(data) as u32) as f64),
+        8 => Some(f64::from_bits(uint(data))),
+        _ => None,
+    }
+}
+
+fn string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).trim_right_matches('\0').to_owned()
+}
+
+/// Size and HDR format of a video track.
+fn video(track: &mut Track, video: &[u8]) -> io::Result<()> {
+    for (id, value) in children(video)? {
+        match id {
+            PIXEL_WIDTH => track.width = Some(uint(value) as u32),
+            PIXEL_HEIGHT => track.height = Some(uint(value) as u32),
+            COLOUR => {
+                for (id, value) in children(value)? {
+                    if id == TRANSFER_CHARACTERISTICS {
+                        set_transfer(track, uint(value));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_dolby_vision(mapping: &[u8]) -> io::Result<bool> {
+    Ok(children(mapping)?
+        .iter()
+        .any(|&(id, value)| id == BLOCK_ADD_ID_TYPE && DOLBY_VISION.contains(&uint(value))))
+}
+
+fn track(entry: &[u8]) -> io::Result<Option<Track>> {
+    let mut kind = None;
+    let mut track = new_track(TrackKind::Video, String::new(), None);
+    // English and default are the default values of Matroska tracks.
+    let mut lang = "eng".to_owned();
+    let mut lang_ietf = None;
+    let mut dolby_vision = false;
+    track.default = true;
+    for (id, value) in children(entry)? {
+        match id {
+            TRACK_TYPE => {
+                kind = match uint(value) {
+                    1 => Some(TrackKind::Video),
+                    2 => Some(TrackKind::Audio),
+                    17 => Some(TrackKind::Subtitle),
+                    _ => None,
+                }
+            }
+            CODEC_ID => track.codec = string(value),
+            NAME if !value.is_empty() => track.name = Some(string(value)),
+            LANGUAGE => lang = string(value),
+            LANGUAGE_IETF => lang_ietf = Some(string(value)),
+            FLAG_DEFAULT => track.default = uint(value) != 0,
+            FLAG_FORCED => track.forced = uint(value) != 0,
+            VIDEO => video(&mut track, value)?,
+            AUDIO => {
+                for (id, value) in children(value)? {
+                    if id == CHANNELS {
+                        track.channels = Some(uint(value) as u32);
+                    }
+                }
+            }
+            BLOCK_ADDITION_MAPPING => dolby_vision = dolby_vision || is_dolby_vision(value)?,
+            _ => {}
+        }
+    }
+    if dolby_vision {
+        track.hdr = Some(Hdr::DolbyVision);
+        track.sdr = false;
+    }
+    // The IETF tag replaces the older ISO 639-2 code when present.
+    track.lang = language(lang_ietf.as_ref().unwrap_or(&lang));
+    Ok(kind.map(|kind| Track { kind, ..track }))
+}
+
+/// Duration of the segment, in seconds.
+fn duration(info: &[u8]) -> io::Result<Option<f64>> {
+    let mut scale = DEFAULT_TIMESTAMP_SCALE;
+    let mut duration = None;
+    for (id, value) in children(info)? {
+        match id {
+            TIMESTAMP_SCALE => scale = uint(value),
+            DURATION => duration = float(value),
+            _ => {}
+        }
+    }
+    Ok(duration.map(|duration| duration * scale as f64 / 1e9))
+}
+
+/// Read a child element of the segment in memory.
+fn body<R: Read>(reader: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    if size > MAX_HEADER_SIZE {
+        return Err(invalid("Matroska header is too large"));
+    }
+    let mut body = vec![0; size as usize];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Read the duration and the tracks of a Matroska or WebM file.
+///
+/// Only the headers at the start of the file are read, they are expected before the first
+/// cluster, where muxers put them.
+pub fn read<R: Read + Seek>(reader: &mut R) -> io::Result<MediaInfo> {
+    let (id, size) = header(reader)?;
+    if id != EBML || size == UNKNOWN_SIZE {
+        return Err(invalid("not a Matroska file"));
+    }
+    reader.seek(SeekFrom::Current(size as i64))?;
+    // The size of the segment is not needed, the headers are read before its end anyway.
+    let (id, _) = header(reader)?;
+    if id != SEGMENT {
+        return Err(invalid("missing Matroska segment"));
+    }
+
+    let mut info = MediaInfo::default();
+    let (mut found_info, mut found_tracks) = (false, false);
+    while !found_info || !found_tracks {
+        let (id, size) = match header(reader) {
+            Ok(header) => header,
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        };
+        match id {
+            INFO => {
+                info.duration = duration(&body(reader, size)?)?;
+                found_info = true;
+            }
+            TRACKS => {
+                for (id, entry) in children(&body(reader, size)?)? {
+                    if id == TRACK_ENTRY {
+                        info.tracks.extend(track(entry)?);
+                    }
+                }
+                found_tracks = true;
+            }
+            CLUSTER => break,
+            _ if size > ::std::i64::MAX as u64 => return Err(invalid("invalid Matroska element")),
+            _ => {
+                reader.seek(SeekFrom::Current(size as i64))?;
+            }
+        }
+    }
+    Ok(info)
+}
+
+/// Encode an element, with a size on 8 bytes.
+#[cfg(test)]
+fn element(id: u32, body: &[u8]) -> Vec<u8> {
+    let mut data = vec![];
+    for shift in &[24, 16, 8, 0] {
+        let byte = (id >> shift) as u8;
+        if byte != 0 || !data.is_empty() {
+            data.push(byte);
+        }
+    }
+    data.push(0x01);
+    for shift in (0..7).rev() {
+        data.push((body.len() as u64 >> (shift * 8)) as u8);
+    }
+    data.extend(body);
+    data
+}
+
+#[test]
+fn test_read() {
+    let audio = [
+        element(TRACK_TYPE, &[2]),
+        element(CODEC_ID, b"A_AC3"),
+        element(LANGUAGE, b"fre"),
+        element(NAME, b"Surround 5.1"),
+        element(AUDIO, &element(CHANNELS, &[6])),
+    ].concat();
+    let subtitle = [
+        element(TRACK_TYPE, &[17]),
+        element(CODEC_ID, b"S_TEXT/UTF8"),
+        element(LANGUAGE, b"eng"),
+        element(LANGUAGE_IETF, b"en-US"),
+        element(FLAG_DEFAULT, &[0]),
+        element(FLAG_FORCED, &[1]),
+    ].concat();
+    let colour = element(COLOUR, &element(TRANSFER_CHARACTERISTICS, &[16]));
+    let video = [
+        element(TRACK_TYPE, &[1]),
+        element(CODEC_ID, b"V_MPEGH/ISO/HEVC"),
+        element(
+            VIDEO,
+            &[element(PIXEL_WIDTH, &[0x0F, 0x00]), element(PIXEL_HEIGHT, &[0x06, 0x48]), colour]
+                .concat(),
+        ),
+    ].concat();
+    let tracks = [
+        element(TRACK_ENTRY, &video),
+        element(TRACK_ENTRY, &audio),
+        element(TRACK_ENTRY, &subtitle),
+    ].concat();
+    // 8160 seconds, with the default scale of a millisecond.
+    let duration = element(DURATION, &[0x41, 0x5F, 0x20, 0xC0, 0x00, 0x00, 0x00, 0x00]);
+    let segment = [
+        element(0x114D_9B74, b"seek head"),
+        element(INFO, &duration),
+        element(TRACKS, &tracks),
+        element(CLUSTER, b"frames"),
+    ].concat();
+    let file = [element(EBML, b"header"), element(SEGMENT, &segment)].concat();
+
+    let info = read(&mut Cursor::new(file)).unwrap();
+    assert_eq!(info.duration, Some(8160.0));
+    let tracks = info.tracks;
+    assert_eq!(tracks.len(), 3);
+    assert_eq!(tracks[0].kind, TrackKind::Video);
+    assert_eq!((tracks[0].width, tracks[0].height), (Some(3840), Some(1608)));
+    assert_eq!(tracks[0].hdr, Some(Hdr::Hdr10));
+    assert_eq!(
+        tracks[1],
+        Track {
+            kind: TrackKind::Audio,
+            codec: "A_AC3".into(),
+            lang: Some("fr".into()),
+            name: Some("Surround 5.1".into()),
+            default: true,
+            forced: false,
+            width: None,
+            height: None,
+            hdr: None,
+            sdr: false,
+            channels: Some(6),
+        }
+    );
+    assert_eq!(tracks[2].lang, Some("en".into()));
+    assert!(!tracks[2].default);
+    assert!(tracks[2].forced);
+
+    assert!(read(&mut Cursor::new(b"not a video".to_vec())).is_err());
+}
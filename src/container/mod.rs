@@ -0,0 +1,286 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use database::{Hdr, Mismatch, Movie, Track, TrackKind};
+use error;
+use parse::release::ReleaseInfo;
+use subtitle;
+
+pub mod avi;
+pub mod mkv;
+pub mod mp4;
+
+/// Codec families, with the names of their codecs in Matroska, MP4 and AVI files.
+static CODECS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("h264", &["v_mpeg4/iso/avc", "avc1", "avc3", "h264", "x264"]),
+    ("h265", &["v_mpegh/iso/hevc", "hvc1", "hev1", "dvh1", "dvhe", "hevc", "x265"]),
+    ("av1", &["v_av1", "av01"]),
+    ("vp9", &["v_vp9", "vp09"]),
+    ("mpeg4", &["v_mpeg4/iso/asp", "v_mpeg4/iso/sp", "mp4v", "xvid", "divx", "dx50"]),
+    ("mpeg2", &["v_mpeg2", "mp2v", "mpg2"]),
+    ("aac", &["a_aac", "mp4a", "aac"]),
+    ("ac3", &["a_ac3", "ac-3", "ac3"]),
+    ("eac3", &["a_eac3", "ec-3", "eac3"]),
+    ("dts", &["a_dts", "dts"]),
+    ("truehd", &["a_truehd", "mlpa", "truehd"]),
+    ("flac", &["a_flac", "flac"]),
+    ("opus", &["a_opus", "opus"]),
+    ("vorbis", &["a_vorbis", "vorbis"]),
+    ("mp3", &["a_mpeg/l3", ".mp3", "mp3"]),
+];
+
+/// Technical information found in the headers of a video file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+    /// In seconds.
+    pub duration: Option<f64>,
+    /// Overall bitrate, in bits per second, from the size of the file and its duration.
+    pub bitrate: Option<u64>,
+    pub tracks: Vec<Track>,
+}
+
+impl MediaInfo {
+    fn track(&self, kind: TrackKind) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.kind == kind)
+    }
+
+    /// The main video track, the first one.
+    pub fn video(&self) -> Option<&Track> {
+        self.track(TrackKind::Video)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// A track with only the fields common to every kind of track set.
+fn new_track(kind: TrackKind, codec: String, lang: Option<String>) -> Track {
+    Track {
+        kind,
+        codec,
+        lang,
+        name: None,
+        default: false,
+        forced: false,
+        width: None,
+        height: None,
+        hdr: None,
+        sdr: false,
+        channels: None,
+    }
+}
+
+/// Set the HDR format of a video track from the transfer characteristics of its colour
+/// information, as defined by ITU-T H.273. 0 and 2 are reserved and unspecified.
+fn set_transfer(track: &mut Track, transfer: u64) {
+    track.hdr = match transfer {
+        16 => Some(Hdr::Hdr10),
+        18 => Some(Hdr::Hlg),
+        _ => None,
+    };
+    track.sdr = track.hdr.is_none() && transfer != 0 && transfer != 2;
+}
+
+/// Normalize the language of a track, `fre`, `fra` and `fr-CA` all become `fr`. Codes without an
+/// ISO 639-1 equivalent are kept, undetermined languages are dropped.
+pub fn language(code: &str) -> Option<String> {
+    let primary = code.split(|c| c == '-' || c == '_').next().unwrap_or("").to_lowercase();
+    match primary.as_str() {
+        "" | "und" | "mis" | "mul" | "zxx" => None,
+        _ => Some(subtitle::language(&primary).map_or(primary.clone(), String::from)),
+    }
+}
+
+/// Read the technical information of a Matroska, WebM, MP4 or AVI file from its headers.
+pub fn read(path: &Path) -> Result<MediaInfo, error::Error> {
+    let ext = path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let reader = || File::open(path).map(BufReader::new);
+    let mut info = match ext.as_str() {
+        "mkv" | "webm" => mkv::read(&mut reader()?)?,
+        "mp4" | "m4v" | "mov" => mp4::read(&mut reader()?)?,
+        "avi" => avi::read(&mut reader()?)?,
+        _ => {
+            let what = format!("reading the headers of {}", path.display());
+            return Err(error::Error::Unsupported(what));
+        }
+    };
+    if let Some(duration) = info.duration {
+        if duration > 0.0 {
+            let size = path.metadata()?.len();
+            info.bitrate = Some((size as f64 * 8.0 / duration) as u64);
+        }
+    }
+    Ok(info)
+}
+
+/// Record the information of a file on its movie, with the claims of its release that disagree
+/// with it.
+pub fn update_movie(movie: &mut Movie, info: MediaInfo) {
+    movie.mismatches = mismatches(&movie.release, &info);
+    movie.duration = info.duration.map(|duration| duration.round() as u32);
+    movie.bitrate = info.bitrate;
+    movie.tracks = info.tracks;
+}
+
+/// Family of a codec as named by the containers, the names used in filenames.
+pub fn codec_family(codec: &str) -> Option<&'static str> {
+    let codec = codec.to_lowercase();
+    CODECS
+        .iter()
+        .find(|&&(_, names)| {
+            names.iter().any(|name| {
+                // Matroska codec ids have variants, such as `A_AAC/MPEG4/LC`.
+                codec == *name || codec.starts_with(&format!("{}/", name))
+            })
+        })
+        .map(|&(family, _)| family)
+}
+
+/// Layout of the channels, such as `5.1` for 6 channels.
+pub fn channel_layout(channels: u32) -> String {
+    match channels {
+        0 => "0".into(),
+        1 | 2 => format!("{}.0", channels),
+        _ => format!("{}.1", channels - 1),
+    }
+}
+
+/// Resolution class of the video, such as `1080p`. The width is also checked, wide movies are
+/// less than 1080 pixels high.
+pub fn resolution(width: u32, height: u32) -> &'static str {
+    if width >= 3200 || height >= 2000 {
+        "2160p"
+    } else if width >= 1800 || height >= 1000 {
+        "1080p"
+    } else if width >= 1200 || height >= 700 {
+        "720p"
+    } else if height >= 540 {
+        "576p"
+    } else if width >= 640 || height >= 460 {
+        "480p"
+    } else if height >= 340 {
+        "360p"
+    } else {
+        "240p"
+    }
+}
+
+fn is_family(codec: &str) -> bool {
+    CODECS.iter().any(|&(family, _)| family == codec)
+}
+
+/// Compare the claims of the release, resolution, codecs and HDR, with the information of the
+/// file. Claims that cannot be checked are ignored.
+pub fn mismatches(release: &ReleaseInfo, info: &MediaInfo) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    let video = info.video();
+
+    let claimed = release.resolution.as_ref();
+    let size = video.and_then(|v| Some((v.width?, v.height?)));
+    if let (Some(claimed), Some((width, height))) = (claimed, size) {
+        let actual = resolution(width, height);
+        if claimed != actual {
+            mismatches.push(Mismatch::Resolution {
+                claimed: claimed.clone(),
+                actual: format!("{} ({}x{})", actual, width, height),
+            });
+        }
+    }
+
+    let claimed = release.codec.as_ref().map(String::as_str);
+    let actual = video.and_then(|v| codec_family(&v.codec));
+    if let (Some(claimed), Some(actual)) = (claimed, actual) {
+        if claimed != actual && is_family(claimed) {
+            mismatches.push(Mismatch::VideoCodec {
+                claimed: claimed.into(),
+                actual: actual.into(),
+            });
+        }
+    }
+
+    let claimed = release.audio.as_ref().map(String::as_str);
+    let actual: Vec<&str> = info.tracks
+        .iter()
+        .filter(|t| t.kind == TrackKind::Audio)
+        .filter_map(|t| codec_family(&t.codec))
+        .collect();
+    if let Some(claimed) = claimed {
+        if !actual.is_empty() && !actual.contains(&claimed) && is_family(claimed) {
+            mismatches.push(Mismatch::AudioCodec {
+                claimed: claimed.into(),
+                actual: actual.join(", "),
+            });
+        }
+    }
+
+    if let (Some(claimed), Some(video)) = (release.hdr, video) {
+        if video.sdr {
+            mismatches.push(Mismatch::Hdr {
+                claimed: claimed.to_string(),
+            });
+        }
+    }
+    mismatches
+}
+
+#[test]
+fn test_language() {
+    assert_eq!(language("fre"), Some("fr".into()));
+    assert_eq!(language("fr-CA"), Some("fr".into()));
+    assert_eq!(language("tlh"), Some("tlh".into()));
+    assert_eq!(language("und"), None);
+}
+
+#[test]
+fn test_resolution() {
+    assert_eq!(resolution(1920, 800), "1080p");
+    assert_eq!(resolution(3840, 1608), "2160p");
+    assert_eq!(resolution(1280, 720), "720p");
+    assert_eq!(resolution(720, 576), "576p");
+    assert_eq!(resolution(720, 480), "480p");
+}
+
+#[test]
+fn test_mismatches() {
+    let mut video = new_track(TrackKind::Video, "V_MPEG4/ISO/AVC".into(), None);
+    video.width = Some(1280);
+    video.height = Some(720);
+    set_transfer(&mut video, 1);
+    let audio = new_track(TrackKind::Audio, "A_AAC".into(), None);
+    let mut info = MediaInfo {
+        tracks: vec![video, audio],
+        ..MediaInfo::default()
+    };
+
+    let release = |filename| ::parse::movie::parse_release(filename);
+    assert!(mismatches(&release("The.Matrix.1999.720p.x264.aac"), &info).is_empty());
+    assert!(mismatches(&release("The.Matrix.1999"), &info).is_empty());
+    assert_eq!(
+        mismatches(&release("The.Matrix.1999.1080p.HDR.x265.AC3"), &info),
+        vec![
+            Mismatch::Resolution {
+                claimed: "1080p".into(),
+                actual: "720p (1280x720)".into(),
+            },
+            Mismatch::VideoCodec {
+                claimed: "h265".into(),
+                actual: "h264".into(),
+            },
+            Mismatch::AudioCodec {
+                claimed: "ac3".into(),
+                actual: "aac".into(),
+            },
+            Mismatch::Hdr {
+                claimed: "HDR10".into(),
+            },
+        ]
+    );
+
+    // Without colour information, HDR may be signalled in the video stream.
+    set_transfer(&mut info.tracks[0], 2);
+    assert!(mismatches(&release("The.Matrix.1999.720p.HDR"), &info).is_empty());
+}
@@ -0,0 +1,300 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use database::{Hdr, Track, TrackKind};
+use super::{invalid, language, new_track, set_transfer, MediaInfo};
+
+/// The movie box holds the sample tables of the whole file, which take a few megabytes for long
+/// movies. A larger one means a corrupted file.
+const MAX_MOVIE_SIZE: u64 = 64 * 1024 * 1024;
+
+type BoxType = [u8; 4];
+
+/// Size of the fields of the sample entries before their child boxes.
+const VISUAL_SAMPLE_ENTRY_SIZE: usize = 86;
+
+/// Read the size of the body and the type of a box. `None` as size means that the box extends
+/// to the end of the file.
+fn header<R: Read>(reader: &mut R) -> io::Result<(Option<u64>, BoxType)> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    let size = u32_at(&bytes, 0) as u64;
+    let kind = [bytes[4], bytes[5], bytes[6], bytes[7]];
+    match size {
+        0 => Ok((None, kind)),
+        1 => {
+            reader.read_exact(&mut bytes)?;
+            let size = u64_at(&bytes, 0);
+            if size < 16 {
+                return Err(invalid("invalid MP4 box size"));
+            }
+            Ok((Some(size - 16), kind))
+        }
+        _ if size < 8 => Err(invalid("invalid MP4 box size")),
+        _ => Ok((Some(size - 8), kind)),
+    }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    (data[pos] as u16) << 8 | data[pos + 1] as u16
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    (u16_at(data, pos) as u32) << 16 | u16_at(data, pos + 2) as u32
+}
+
+fn u64_at(data: &[u8], pos: usize) -> u64 {
+    (u32_at(data, pos) as u64) << 32 | u32_at(data, pos + 4) as u64
+}
+
+/// The child boxes in the body of a box, as types and bodies.
+fn children(data: &[u8]) -> io::Result<Vec<(BoxType, &[u8])>> {
+    let mut found = vec![];
+    let mut rest = data;
+    while rest.len() >= 8 {
+        let (size, kind) = header(&mut &rest[..])?;
+        let start = if u32_at(rest, 0) == 1 { 16 } else { 8 };
+        let end = match size {
+            Some(size) if size <= (rest.len() - start) as u64 => start + size as usize,
+            Some(_) => return Err(invalid("truncated MP4 box")),
+            None => rest.len(),
+        };
+        found.push((kind, &rest[start..end]));
+        rest = &rest[end..];
+    }
+    Ok(found)
+}
+
+fn child<'a>(data: &'a [u8], kind: &BoxType) -> io::Result<Option<&'a [u8]>> {
+    Ok(children(data)?
+        .into_iter()
+        .find(|&(k, _)| &k == kind)
+        .map(|(_, body)| body))
+}
+
+/// Language of the media header, three letters packed in 15 bits. QuickTime files may use older
+/// Macintosh language codes instead, below 0x400, which are ignored.
+fn mdhd_language(mdhd: &[u8]) -> Option<String> {
+    let pos = if mdhd.first() == Some(&1) { 32 } else { 20 };
+    if mdhd.len() < pos + 2 {
+        return None;
+    }
+    let packed = u16_at(mdhd, pos) & 0x7FFF;
+    if packed < 0x400 {
+        return None;
+    }
+    let code: String = [10, 5, 0]
+        .iter()
+        .map(|shift| (((packed >> shift) & 0x1F) as u8 + 0x60) as char)
+        .collect();
+    language(&code)
+}
+
+/// First sample description, which starts with its size and its codec, such as `avc1`.
+fn sample_entry(stsd: &[u8]) -> Option<&[u8]> {
+    // Version and flags, then the entry count.
+    let entry = stsd.get(8..)?;
+    if entry.len() < 8 {
+        return None;
+    }
+    let size = (u32_at(entry, 0) as usize).min(entry.len());
+    Some(&entry[..size.max(8)])
+}
+
+/// Set the HDR format of the track from its visual sample entry, from its `nclx` colour
+/// information or its Dolby Vision configuration.
+fn entry_hdr(track: &mut Track, entry: &[u8]) -> io::Result<()> {
+    let mut dolby_vision = &entry[4..8] == b"dvh1" || &entry[4..8] == b"dvhe";
+    if let Some(boxes) = entry.get(VISUAL_SAMPLE_ENTRY_SIZE..) {
+        for (kind, body) in children(boxes)? {
+            match &kind {
+                b"dvcC" | b"dvvC" => dolby_vision = true,
+                b"colr" if body.len() >= 8 && &body[..4] == b"nclx" => {
+                    set_transfer(track, u16_at(body, 6) as u64);
+                }
+                _ => {}
+            }
+        }
+    }
+    if dolby_vision {
+        track.hdr = Some(Hdr::DolbyVision);
+        track.sdr = false;
+    }
+    Ok(())
+}
+
+fn track(trak: &[u8]) -> io::Result<Option<Track>> {
+    let mdia = match child(trak, b"mdia")? {
+        Some(mdia) => mdia,
+        None => return Ok(None),
+    };
+    let handler = child(mdia, b"hdlr")?
+        .and_then(|hdlr| hdlr.get(8..12))
+        .map(|handler| String::from_utf8_lossy(handler).into_owned());
+    let kind = match handler.as_ref().map(String::as_str) {
+        Some("vide") => TrackKind::Video,
+        Some("soun") => TrackKind::Audio,
+        Some("sbtl") | Some("subt") | Some("text") | Some("clcp") => TrackKind::Subtitle,
+        _ => return Ok(None),
+    };
+    let lang = child(mdia, b"mdhd")?.and_then(mdhd_language);
+    let mut track = new_track(kind, String::new(), lang);
+
+    let stbl = child(mdia, b"minf")?.map_or(Ok(None), |minf| child(minf, b"stbl"))?;
+    let stsd = stbl.map_or(Ok(None), |stbl| child(stbl, b"stsd"))?;
+    if let Some(entry) = stsd.and_then(sample_entry) {
+        track.codec = String::from_utf8_lossy(&entry[4..8]).trim().to_owned();
+        match kind {
+            TrackKind::Video if entry.len() >= 36 => {
+                track.width = Some(u16_at(entry, 32) as u32);
+                track.height = Some(u16_at(entry, 34) as u32);
+                entry_hdr(&mut track, entry)?;
+            }
+            TrackKind::Audio if entry.len() >= 26 => {
+                track.channels = Some(u16_at(entry, 24) as u32);
+            }
+            _ => {}
+        }
+    }
+    // The first flag of the track header means that the track is enabled, which players treat as
+    // the default track.
+    track.default = child(trak, b"tkhd")?
+        .and_then(|tkhd| tkhd.get(3).cloned())
+        .map_or(false, |flags| flags & 1 != 0);
+    Ok(Some(track))
+}
+
+/// Duration of the movie header, in seconds.
+fn mvhd_duration(mvhd: &[u8]) -> Option<f64> {
+    let (timescale, duration) = if mvhd.first() == Some(&1) && mvhd.len() >= 32 {
+        (u32_at(mvhd, 20), u64_at(mvhd, 24))
+    } else if mvhd.len() >= 20 {
+        (u32_at(mvhd, 12), u32_at(mvhd, 16) as u64)
+    } else {
+        return None;
+    };
+    if timescale == 0 {
+        None
+    } else {
+        Some(duration as f64 / timescale as f64)
+    }
+}
+
+/// Read the duration and the tracks of a MP4 or QuickTime file.
+///
+/// The movie box is at the start of files prepared for streaming and at the end of the others,
+/// the media data before it is skipped.
+pub fn read<R: Read + Seek>(reader: &mut R) -> io::Result<MediaInfo> {
+    let mut first = true;
+    loop {
+        let (size, kind) = match header(reader) {
+            Ok(header) => header,
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof && !first => {
+                return Err(invalid("missing MP4 movie box"))
+            }
+            Err(err) => return Err(err),
+        };
+        let known = [b"ftyp", b"moov", b"mdat", b"free", b"skip", b"wide", b"pnot"];
+        if first && !known.iter().any(|&k| k == &kind) {
+            return Err(invalid("not a MP4 file"));
+        }
+        first = false;
+        match (size, &kind) {
+            (Some(size), b"moov") if size <= MAX_MOVIE_SIZE => {
+                let mut body = vec![0; size as usize];
+                reader.read_exact(&mut body)?;
+                let mut info = MediaInfo::default();
+                for (kind, child) in children(&body)? {
+                    match &kind {
+                        b"mvhd" => info.duration = mvhd_duration(child),
+                        b"trak" => info.tracks.extend(track(child)?),
+                        _ => {}
+                    }
+                }
+                return Ok(info);
+            }
+            (_, b"moov") => return Err(invalid("MP4 movie box is too large")),
+            (Some(size), _) if size <= ::std::i64::MAX as u64 => {
+                reader.seek(SeekFrom::Current(size as i64))?;
+            }
+            _ => return Err(invalid("missing MP4 movie box")),
+        }
+    }
+}
+
+#[cfg(test)]
+fn mp4_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+    let size = body.len() as u32 + 8;
+    let mut data = vec![(size >> 24) as u8, (size >> 16) as u8, (size >> 8) as u8, size as u8];
+    data.extend(kind);
+    data.extend(body);
+    data
+}
+
+#[cfg(test)]
+fn test_trak(handler: &[u8], entry: &[u8], lang: u16, enabled: bool) -> Vec<u8> {
+    let tkhd = [0, 0, 0, enabled as u8];
+    let mut mdhd = vec![0; 20];
+    mdhd.extend(&[(lang >> 8) as u8, lang as u8, 0, 0]);
+    let hdlr = [&[0u8; 8][..], handler, &[0u8; 13][..]].concat();
+    let stsd = [&[0, 0, 0, 0, 0, 0, 0, 1][..], entry].concat();
+    let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+    let mdia = [
+        mp4_box(b"mdhd", &mdhd),
+        mp4_box(b"hdlr", &hdlr),
+        mp4_box(b"minf", &stbl),
+    ].concat();
+    mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mp4_box(b"mdia", &mdia)].concat())
+}
+
+#[test]
+fn test_read() {
+    use std::io::Cursor;
+
+    // 1920x800 with HDR10 colour information.
+    let mut video = vec![0; VISUAL_SAMPLE_ENTRY_SIZE - 8];
+    video[24..28].copy_from_slice(&[0x07, 0x80, 0x03, 0x20]);
+    video.extend(mp4_box(b"colr", &[b'n', b'c', b'l', b'x', 0, 9, 0, 16, 0, 9, 0]));
+    let video = mp4_box(b"hvc1", &video);
+    let mut audio = vec![0; 20];
+    audio[17] = 6;
+    let audio = mp4_box(b"mp4a", &audio);
+    let text = mp4_box(b"tx3g", &[0; 8]);
+
+    // `fra` and `eng` packed in 15 bits.
+    let fra = (6 << 10) | (18 << 5) | 1;
+    let eng = (5 << 10) | (14 << 5) | 7;
+    // Version 0, creation and modification times, a timescale of 1000 and 2 hours.
+    let mvhd = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x03, 0xE8, 0, 0x6D, 0xDD, 0x00];
+    let moov = [
+        mp4_box(b"mvhd", &mvhd),
+        test_trak(b"vide", &video, 0x55C4, true),
+        test_trak(b"soun", &audio, fra, true),
+        test_trak(b"sbtl", &text, eng, false),
+    ].concat();
+    // The movie box is after the media data.
+    let file = [
+        mp4_box(b"ftyp", b"isom"),
+        mp4_box(b"mdat", b"frames"),
+        mp4_box(b"moov", &moov),
+    ].concat();
+
+    let info = read(&mut Cursor::new(file)).unwrap();
+    assert_eq!(info.duration, Some(7200.0));
+    let tracks = info.tracks;
+    assert_eq!(tracks.len(), 3);
+    assert_eq!(tracks[0].kind, TrackKind::Video);
+    assert_eq!(tracks[0].codec, "hvc1");
+    assert_eq!(tracks[0].lang, None);
+    assert_eq!((tracks[0].width, tracks[0].height), (Some(1920), Some(800)));
+    assert_eq!(tracks[0].hdr, Some(Hdr::Hdr10));
+    assert_eq!(tracks[1].kind, TrackKind::Audio);
+    assert_eq!(tracks[1].codec, "mp4a");
+    assert_eq!(tracks[1].lang, Some("fr".into()));
+    assert_eq!(tracks[1].channels, Some(6));
+    assert!(tracks[1].default);
+    assert_eq!(tracks[2].kind, TrackKind::Subtitle);
+    assert_eq!(tracks[2].lang, Some("en".into()));
+    assert!(!tracks[2].default);
+
+    assert!(read(&mut Cursor::new(b"not a video file".to_vec())).is_err());
+}
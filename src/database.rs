@@ -1,32 +1,50 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
 
 use serde_json;
 
+use config::Config;
 use error;
+use parse::release::ReleaseInfo;
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+/// Templates the versions storing them in the database started with. They are not moved to the
+/// configuration, so that these libraries use the current default templates.
+static OLD_DEFAULT_TEMPLATES: &'static [&'static str] = &[
+    "{title} ({year})/{title} ({year}){version}.{ext}",
+    concat!(
+        "{show} ({show_year})/{season_dir}/",
+        "{show} - S{season}E{episode} - {episode_title}{version}.{ext}"
+    ),
+];
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Database {
-    movies_path: PathBuf,
-    tv_path: PathBuf,
+    // The library paths and templates used to be stored in the database, they now live in the
+    // configuration.
+    #[serde(default, skip_serializing)]
+    movies_path: Option<PathBuf>,
+    #[serde(default, skip_serializing)]
+    tv_path: Option<PathBuf>,
+    #[serde(default, skip_serializing)]
+    movie_template: Option<String>,
+    #[serde(default, skip_serializing)]
+    episode_template: Option<String>,
     movies: Vec<Movie>,
     movies_index: HashMap<String, usize>,
+    #[serde(default)]
+    shows: Vec<Show>,
+    #[serde(default)]
+    episodes: Vec<Episode>,
+    #[serde(default)]
+    episodes_index: HashMap<String, usize>,
 }
 
 impl Database {
-    pub fn new<A1, A2>(movies_path: A1, tv_path: A2) -> Database
-    where
-        A1: AsRef<Path>,
-        A2: AsRef<Path>,
-    {
-        Database {
-            movies: vec![],
-            movies_path: movies_path.as_ref().to_owned(),
-            tv_path: tv_path.as_ref().to_owned(),
-            movies_index: HashMap::new(),
-        }
+    pub fn new() -> Database {
+        Database::default()
     }
 
     pub fn open<A>(path: A) -> Result<Option<Database>, error::Error>
@@ -51,12 +69,35 @@ impl Database {
         Ok(())
     }
 
-    pub fn movies_path(&self) -> &Path {
-        &self.movies_path
-    }
-
-    pub fn tv_path(&self) -> &Path {
-        &self.tv_path
+    /// Move the library paths and templates stored by older versions into the configuration,
+    /// unless the configuration already has them. Returns true if the configuration was changed.
+    pub fn migrate_settings(&mut self, config: &mut Config) -> bool {
+        let mut changed = false;
+        if let Some(path) = self.movies_path.take() {
+            if config.movies.path.is_none() {
+                config.movies.path = Some(path);
+                changed = true;
+            }
+        }
+        if let Some(path) = self.tv_path.take() {
+            if config.tv.path.is_none() {
+                config.tv.path = Some(path);
+                changed = true;
+            }
+        }
+        if let Some(template) = self.movie_template.take() {
+            if config.movies.template.is_none() && !OLD_DEFAULT_TEMPLATES.contains(&&*template) {
+                config.movies.template = Some(template);
+                changed = true;
+            }
+        }
+        if let Some(template) = self.episode_template.take() {
+            if config.tv.template.is_none() && !OLD_DEFAULT_TEMPLATES.contains(&&*template) {
+                config.tv.template = Some(template);
+                changed = true;
+            }
+        }
+        changed
     }
 
     pub fn add_movie(&mut self, movie: Movie) -> &Movie {
@@ -73,18 +114,76 @@ impl Database {
             .and_then(|&idx| self.movies.get(idx))
     }
 
-    pub fn duplicates(&self, tmdb_id: i64) -> Vec<&Movie> {
+    pub fn movies(&self) -> &[Movie] {
+        &self.movies
+    }
+
+    pub fn movies_mut(&mut self) -> &mut [Movie] {
+        &mut self.movies
+    }
+
+    /// Every copy of the movie, of every edition.
+    pub fn copies(&self, tmdb_id: i64) -> Vec<&Movie> {
         self.movies
             .iter()
             .filter(|m| m.tmdb_id == tmdb_id)
             .collect()
     }
 
+    /// Copies of the same edition of the movie, editions are not duplicates of each other.
+    pub fn duplicates(&self, tmdb_id: i64, edition: Option<&str>) -> Vec<&Movie> {
+        self.movies
+            .iter()
+            .filter(|m| m.tmdb_id == tmdb_id && m.is_edition(edition))
+            .collect()
+    }
+
+    pub fn show(&self, tmdb_id: i64) -> Option<&Show> {
+        self.shows.iter().find(|s| s.tmdb_id == tmdb_id)
+    }
+
+    /// Add the show if it is not already in the database.
+    pub fn add_show(&mut self, show: Show) -> &Show {
+        let idx = match self.shows.iter().position(|s| s.tmdb_id == show.tmdb_id) {
+            Some(idx) => idx,
+            None => {
+                self.shows.push(show);
+                self.shows.len() - 1
+            }
+        };
+        &self.shows[idx]
+    }
+
+    pub fn add_episode(&mut self, episode: Episode) -> &Episode {
+        let fingerprint = episode.fingerprint.clone();
+        self.episodes.push(episode);
+        let idx = self.episodes.len() - 1;
+        self.episodes_index.insert(fingerprint, idx);
+        &self.episodes[idx]
+    }
+
+    pub fn match_episode_fingerprint<'db>(&'db self, fingerprint: &str) -> Option<&'db Episode> {
+        self.episodes_index
+            .get(fingerprint)
+            .and_then(|&idx| self.episodes.get(idx))
+    }
+
+    pub fn episode_duplicates(&self, show_id: i64, season: i32, episodes: &[i32]) -> Vec<&Episode> {
+        self.episodes
+            .iter()
+            .filter(|e| e.show_id == show_id && e.season == season && e.episodes == episodes)
+            .collect()
+    }
+
     pub fn rebuild_index(&mut self) {
         self.movies_index.clear();
         for (idx, movie) in self.movies.iter().enumerate() {
             self.movies_index.insert(movie.fingerprint.clone(), idx);
         }
+        self.episodes_index.clear();
+        for (idx, episode) in self.episodes.iter().enumerate() {
+            self.episodes_index.insert(episode.fingerprint.clone(), idx);
+        }
     }
 
     pub fn retain_movies<F>(&mut self, func: F)
@@ -94,6 +193,18 @@ impl Database {
         self.movies.retain(func);
         self.rebuild_index();
     }
+
+    /// Retain episodes matching the predicate. Shows left without episodes are removed.
+    pub fn retain_episodes<F>(&mut self, func: F)
+    where
+        F: FnMut(&Episode) -> bool,
+    {
+        self.episodes.retain(func);
+        let episodes = &self.episodes;
+        self.shows
+            .retain(|show| episodes.iter().any(|e| e.show_id == show.tmdb_id));
+        self.rebuild_index();
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -105,19 +216,176 @@ pub struct Movie {
     pub title: String,
     pub original_title: String,
     pub year: i32,
+    #[serde(default)]
+    pub edition: Option<String>, // Such as `Director's Cut`.
     pub overview: String,
+    #[serde(default)]
+    pub tagline: String,
+    #[serde(default)]
+    pub runtime: Option<i32>, // In minutes.
+    #[serde(default)]
+    pub imdb_id: Option<String>,
+    #[serde(default)]
+    pub certification: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub collection: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<String>, // ISO 639-1 codes of the spoken languages.
+    #[serde(default)]
+    pub directors: Vec<String>,
+    #[serde(default)]
+    pub cast: Vec<String>, // Main actors, in billing order.
     pub path: PathBuf,
     pub subtitles: Vec<Subtitle>,
     pub images: Vec<Image>,
+    #[serde(default)]
+    pub release: ReleaseInfo, // Found in the name of the imported file.
+    #[serde(default)]
+    pub tracks: Vec<Track>, // Tracks embedded in the file, see container::read.
+    #[serde(default)]
+    pub duration: Option<u32>, // Duration of the file, in seconds.
+    #[serde(default)]
+    pub bitrate: Option<u64>, // Overall bitrate of the file, in bits per second.
+    #[serde(default)]
+    pub mismatches: Vec<Mismatch>, // Claims of the filename contradicted by the file.
+}
+
+impl Movie {
+    /// Whether the movie is of the given edition, ignoring the case.
+    pub fn is_edition(&self, edition: Option<&str>) -> bool {
+        match (self.edition.as_ref(), edition) {
+            (Some(own), Some(edition)) => own.to_lowercase() == edition.to_lowercase(),
+            (own, edition) => own.is_none() && edition.is_none(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Show {
+    pub tmdb_id: i64,
+    pub title: String,
+    pub original_title: String,
+    pub year: Option<i32>,
+    pub overview: String,
+    pub path: PathBuf, // Directory of the show, seasons are stored inside of it.
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Episode {
+    pub show_id: i64, // TMDB id of the show.
+    pub season: i32,
+    pub episodes: Vec<i32>, // Multi-episode files contain more than one episode.
+    pub duplicate_index: i32,
+    pub fingerprint: String,
+
+    pub title: Option<String>,
+    pub overview: String,
+    pub path: PathBuf,
+    pub subtitles: Vec<Subtitle>,
+    /// What the name of the imported file tells about the release.
+    #[serde(default)]
+    pub release: ReleaseInfo,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Subtitle {
+    /// ISO 639-1 code of the language.
     pub lang: Option<String>,
+    #[serde(default)]
+    pub forced: bool,
+    /// For the deaf and hard of hearing.
+    #[serde(default)]
+    pub sdh: bool,
     pub path: PathBuf,
 }
 
+/// An audio, video or subtitle track embedded in a video file.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Track {
+    pub kind: TrackKind,
+    /// Codec as named by the container, such as `A_AC3` in Matroska or `mp4a` in MP4.
+    pub codec: String,
+    /// ISO 639-1 code of the language if there is one, otherwise the code found in the file.
+    pub lang: Option<String>,
+    pub name: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+    /// Size of the pictures of video tracks, in pixels.
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub hdr: Option<Hdr>,
+    /// The colour information of the container says the video is SDR. Without it, an HDR format
+    /// may only be signalled in the video stream itself.
+    #[serde(default)]
+    pub sdr: bool,
+    /// Number of channels of audio tracks.
+    #[serde(default)]
+    pub channels: Option<u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum TrackKind {
+    Video,
+    Audio,
+    Subtitle,
+}
+
+/// High dynamic range format of a video track.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Hdr {
+    Hdr10,
+    Hlg,
+    DolbyVision,
+}
+
+impl fmt::Display for Hdr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Hdr::Hdr10 => "HDR10",
+            Hdr::Hlg => "HLG",
+            Hdr::DolbyVision => "Dolby Vision",
+        })
+    }
+}
+
+/// A claim of the filename, such as `1080p` or `x265`, that the file contradicts.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Mismatch {
+    Resolution { claimed: String, actual: String },
+    VideoCodec { claimed: String, actual: String },
+    AudioCodec { claimed: String, actual: String },
+    /// The filename claims HDR but the video is SDR.
+    Hdr { claimed: String },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mismatch::Resolution {
+                ref claimed,
+                ref actual,
+            } => write!(f, "filename claims {}, the video is {}", claimed, actual),
+            Mismatch::VideoCodec {
+                ref claimed,
+                ref actual,
+            } => write!(f, "filename claims {} video, the video is {}", claimed, actual),
+            Mismatch::AudioCodec {
+                ref claimed,
+                ref actual,
+            } => write!(f, "filename claims {} audio, the audio is {}", claimed, actual),
+            Mismatch::Hdr { ref claimed } => {
+                write!(f, "filename claims {}, the video is not HDR", claimed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum ImageKind {
     Poster,
     Backdrop,
@@ -128,3 +396,43 @@ pub struct Image {
     pub kind: ImageKind,
     pub path: PathBuf,
 }
+
+#[test]
+fn test_migrate_settings() {
+    let mut db: Database = serde_json::from_str(
+        r#"{
+            "movies_path": "/media/movies",
+            "tv_path": "/media/tv",
+            "movies": [],
+            "movies_index": {},
+            "movie_template": "{title} ({year})/{title} ({year}){version}.{ext}",
+            "episode_template": "{show}/{show} {season}x{episode}{version}.{ext}"
+        }"#,
+    ).unwrap();
+    let mut config = Config::default();
+    config.tv.path = Some("/data/tv".into());
+    assert!(db.migrate_settings(&mut config));
+    assert_eq!(config.movies.path, Some("/media/movies".into()));
+    assert_eq!(config.tv.path, Some("/data/tv".into()));
+    // The default template of the time is left out, to use the current one.
+    assert_eq!(config.movies.template, None);
+    assert_eq!(
+        config.tv.template,
+        Some("{show}/{show} {season}x{episode}{version}.{ext}".into())
+    );
+    assert!(!db.migrate_settings(&mut config));
+}
+
+#[test]
+fn test_duplicates_edition() {
+    let mut db = Database::new();
+    db.add_movie(Movie {
+        tmdb_id: 78,
+        fingerprint: "a".into(),
+        edition: Some("Director's Cut".into()),
+        ..Movie::default()
+    });
+    assert_eq!(db.duplicates(78, Some("director's cut")).len(), 1);
+    assert!(db.duplicates(78, Some("Final Cut")).is_empty());
+    assert!(db.duplicates(78, None).is_empty());
+}
@@ -1,16 +1,61 @@
+use std::fmt;
 use std::io;
 
 use reqwest;
 use serde_json;
+use toml;
 
+use template;
 use tmdb;
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     Json(serde_json::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
     Http(reqwest::Error),
     TMDB(tmdb::search::Error),
+    /// No TMDB API key was configured.
+    MissingApiKey,
+    /// TMDB rejected the API key.
+    ApiKeyRejected(tmdb::search::Error),
+    /// The metadata provider has nothing with this id.
+    NotFound(String),
+    /// The response of this request is not in the cache and offline mode is enabled.
+    Offline(String),
+    /// A file could not be downloaded, with the URL and the HTTP status.
+    Download(String, u16),
+    /// An operation that is not supported, such as converting subtitles between two formats.
+    Unsupported(String),
+    Template(template::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Json(ref err) => write!(f, "{}", err),
+            Error::TomlDe(ref err) => write!(f, "{}", err),
+            Error::TomlSer(ref err) => write!(f, "{}", err),
+            Error::Http(ref err) => write!(f, "{}", err),
+            Error::TMDB(ref err) => write!(f, "TMDB error: {}", err.status_message),
+            Error::MissingApiKey => write!(
+                f,
+                "no TMDB API key, set api_key in the configuration or MERO_API_KEY"
+            ),
+            Error::ApiKeyRejected(ref err) => {
+                write!(f, "TMDB rejected the API key: {}", err.status_message)
+            }
+            Error::NotFound(ref what) => write!(f, "{} was not found", what),
+            Error::Offline(ref key) => write!(f, "{} is not cached, cannot fetch it offline", key),
+            Error::Download(ref url, status) => {
+                write!(f, "unable to download {} (HTTP {})", url, status)
+            }
+            Error::Unsupported(ref what) => write!(f, "{} is not supported", what),
+            Error::Template(ref err) => write!(f, "{}", err),
+        }
+    }
 }
 
 impl From<io::Error> for Error {
@@ -25,6 +70,18 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Error {
+        Error::TomlDe(err)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Error {
+        Error::TomlSer(err)
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Error {
         Error::Http(err)
@@ -36,3 +93,9 @@ impl From<tmdb::search::Error> for Error {
         Error::TMDB(err)
     }
 }
+
+impl From<template::Error> for Error {
+    fn from(err: template::Error) -> Error {
+        Error::Template(err)
+    }
+}
@@ -59,6 +59,12 @@ impl Entry {
             .unwrap_or(false)
     }
 
+    pub fn is_nfo(&self) -> bool {
+        self.extension()
+            .map(|s| s.eq_ignore_ascii_case("nfo"))
+            .unwrap_or(false)
+    }
+
     pub fn is_subtitle(&self) -> bool {
         self.extension()
             .map(|s| SUBTITLE_FILES.contains(s))
@@ -101,9 +107,43 @@ where
     }
 }
 
-fn walk_rec(path: &Path, tree: &mut Tree<Entry>, parent: Node) -> Result<(), error::Error> {
+/// Match a file name against a glob pattern, ignoring case. `*` matches any number of
+/// characters, `?` matches a single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.first() {
+            None => name.is_empty(),
+            Some(&'*') => (0..name.len() + 1).any(|skip| matches(&pattern[1..], &name[skip..])),
+            Some(&'?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+            Some(&c) => name.first() == Some(&c) && matches(&pattern[1..], &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    matches(&pattern, &name)
+}
+
+fn is_ignored(path: &Path, ignore: &[String]) -> bool {
+    match path.file_name() {
+        Some(name) => {
+            let name = name.to_string_lossy();
+            ignore.iter().any(|pattern| glob_match(pattern, &name))
+        }
+        None => false,
+    }
+}
+
+fn walk_rec(
+    path: &Path,
+    tree: &mut Tree<Entry>,
+    parent: Node,
+    ignore: &[String],
+) -> Result<(), error::Error> {
     for item in path.read_dir()? {
         let item = item?;
+        if is_ignored(&item.path(), ignore) {
+            continue;
+        }
         let abs_path = item.path().canonicalize()?;
         let metadata = item.metadata()?;
         let is_dir = metadata.is_dir();
@@ -112,13 +152,22 @@ fn walk_rec(path: &Path, tree: &mut Tree<Entry>, parent: Node) -> Result<(), err
         tree.append_to(node, parent);
 
         if is_dir {
-            walk_rec(&abs_path, tree, node)?;
+            walk_rec(&abs_path, tree, node, ignore)?;
         }
     }
     Ok(())
 }
 
 pub fn walk<A: AsRef<Path>>(path: A) -> Result<(Tree<Entry>, Node), error::Error> {
+    walk_ignoring(path, &[])
+}
+
+/// Walk the directory, skipping files and directories whose name matches one of the glob
+/// patterns, see `glob_match`.
+pub fn walk_ignoring<A: AsRef<Path>>(
+    path: A,
+    ignore: &[String],
+) -> Result<(Tree<Entry>, Node), error::Error> {
     let abs_path = path.as_ref().canonicalize()?;
     let metadata = abs_path.metadata()?;
     let is_dir = metadata.is_dir();
@@ -127,12 +176,26 @@ pub fn walk<A: AsRef<Path>>(path: A) -> Result<(Tree<Entry>, Node), error::Error
     let root = tree.node(Entry::new(abs_path, metadata));
 
     if is_dir {
-        walk_rec(path.as_ref(), &mut tree, root)?;
+        walk_rec(path.as_ref(), &mut tree, root, ignore)?;
     }
 
     Ok((tree, root))
 }
 
+/// Whether the video is alone in its directory, the files describing it can then use the names
+/// applying to the whole directory, such as `poster.jpg`. The video does not need to exist yet.
+pub fn only_video(video: &Path) -> bool {
+    let entries = match video.parent().map(fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return true,
+    };
+    !entries.filter_map(Result::ok).map(|e| e.path()).any(|path| {
+        path != video
+            && path.extension()
+                .map_or(false, |ext| VIDEO_FILES.contains(&*ext.to_string_lossy()))
+    })
+}
+
 pub fn best_copy<A1, A2>(src: A1, dst: A2) -> io::Result<()>
 where
     A1: AsRef<Path>,
@@ -144,6 +207,60 @@ where
     fs::hard_link(&src, &dst).or_else(|_| fs::copy(&src, &dst).map(|_| ()))
 }
 
+/// How files are transferred from the import directory to the library.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMode {
+    /// Hard link the file, or copy it if the library is on another file system.
+    Auto,
+    Hardlink,
+    Copy,
+    /// Move the file, or copy and delete it if the library is on another file system.
+    Move,
+    Symlink,
+}
+
+impl Default for TransferMode {
+    fn default() -> TransferMode {
+        TransferMode::Auto
+    }
+}
+
+#[cfg(unix)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(windows)]
+fn symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    ::std::os::windows::fs::symlink_file(src, dst)
+}
+
+pub fn transfer<A1, A2>(src: A1, dst: A2, mode: TransferMode) -> io::Result<()>
+where
+    A1: AsRef<Path>,
+    A2: AsRef<Path>,
+{
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    if mode == TransferMode::Auto {
+        return best_copy(src, dst);
+    }
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .create(dst.parent().expect("destination has no directory"))?;
+    match mode {
+        TransferMode::Auto => best_copy(src, dst),
+        TransferMode::Hardlink => fs::hard_link(src, dst),
+        TransferMode::Copy => fs::copy(src, dst).map(|_| ()),
+        TransferMode::Move => fs::rename(src, dst).or_else(|_| {
+            fs::copy(src, dst)?;
+            fs::remove_file(src)
+        }),
+        TransferMode::Symlink => symlink(src, dst),
+    }
+}
+
 pub fn filter_filename(source: &str) -> String {
     let mut dest = String::with_capacity(source.len());
     for car in source.chars() {
@@ -158,6 +275,15 @@ pub fn filter_filename(source: &str) -> String {
     dest
 }
 
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("*.nfo", "Movie.NFO"));
+    assert!(glob_match("sample*", "sample-movie.mkv"));
+    assert!(glob_match("?ubs", "Subs"));
+    assert!(!glob_match("*.nfo", "movie.mkv"));
+    assert!(!glob_match("sample", "samples"));
+}
+
 #[test]
 fn test_filter_filename() {
     assert_eq!(filter_filename("2001: A Space"), "2001_ A Space");
@@ -52,3 +52,68 @@ pub fn question_bool(text: &str) -> bool {
         }
     }
 }
+
+/// Answer to a `question_choice` question.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Choice {
+    /// Index of the chosen candidate, starting at 0.
+    Pick(usize),
+    /// New search query.
+    Query(String),
+    /// Id entered directly.
+    Id(i64),
+    Skip,
+}
+
+fn parse_choice(answer: &str, count: usize) -> Option<Choice> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return None;
+    }
+    if answer == "s" || answer == "S" {
+        return Some(Choice::Skip);
+    }
+    if answer.starts_with('#') {
+        return answer[1..].parse().ok().map(Choice::Id);
+    }
+    // Other numbers are searched for, for titles such as `1917`.
+    match answer.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= count => Some(Choice::Pick(n - 1)),
+        _ => Some(Choice::Query(answer.into())),
+    }
+}
+
+/// Ask the user to pick one of `count` candidates, numbered from 1.
+///
+/// The user can also skip with `s`, enter an id with `#<id>` or type anything else, including
+/// numbers that are not a candidate, to search again.
+pub fn question_choice(text: &str, count: usize) -> Choice {
+    loop {
+        if count > 0 {
+            print_flush!(
+                "{} [1-{}, s to skip, #<id> or a new search] ",
+                text,
+                count
+            );
+        } else {
+            print_flush!("{} [s to skip, #<id> or a new search] ", text);
+        }
+        if let Some(choice) = parse_choice(&input(), count) {
+            return choice;
+        }
+    }
+}
+
+#[test]
+fn test_parse_choice() {
+    assert_eq!(parse_choice("2", 3), Some(Choice::Pick(1)));
+    assert_eq!(parse_choice("1917", 3), Some(Choice::Query("1917".into())));
+    assert_eq!(parse_choice("4", 3), Some(Choice::Query("4".into())));
+    assert_eq!(parse_choice("s", 3), Some(Choice::Skip));
+    assert_eq!(parse_choice("#603", 3), Some(Choice::Id(603)));
+    assert_eq!(
+        parse_choice("the matrix", 3),
+        Some(Choice::Query("the matrix".into()))
+    );
+    assert_eq!(parse_choice("", 3), None);
+}
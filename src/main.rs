@@ -15,27 +15,144 @@ extern crate sha2;
 extern crate slab;
 #[macro_use]
 extern crate structopt;
+extern crate toml;
 
+pub mod classify;
+pub mod config;
+pub mod container;
 pub mod database;
 pub mod error;
 pub mod fingerprint;
 pub mod fs;
 pub mod input;
+pub mod matching;
+pub mod nfo;
 pub mod parse;
+pub mod provider;
+pub mod quality;
+pub mod subtitle;
 pub mod tasks;
+pub mod template;
 pub mod tmdb;
 pub mod tree;
 
+use std::fs::File;
+use std::path::PathBuf;
+use std::process;
+
 use structopt::StructOpt;
 
+use config::Config;
 use database::Database;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "mero", about = "Movie and tv library manager")]
+pub struct Args {
+    /// Path of the configuration file.
+    #[structopt(long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Only use the cached TMDB responses, never the network.
+    #[structopt(long = "offline")]
+    offline: bool,
+    #[structopt(subcommand)]
+    command: Commands,
+}
+
+#[derive(StructOpt, Debug)]
 pub enum Commands {
-    /// Import movies from a directory, moving the files to the library.
+    /// Import movies and tv shows from a directory, moving the files to the library.
     #[structopt(name = "import")]
-    Import { path: String },
+    Import {
+        path: String,
+        /// Ask which match is the right one when the best match's score is below the threshold.
+        #[structopt(short = "i", long = "interactive")]
+        interactive: bool,
+        /// Never ask, files whose best match's score is below the threshold are left for review.
+        #[structopt(long = "unattended", conflicts_with = "interactive")]
+        unattended: bool,
+        /// Minimum score, between 0 and 1, for a match to be accepted without asking.
+        #[structopt(long = "threshold", raw(default_value = "&DEFAULT_THRESHOLD"),
+                    parse(try_from_str = "parse_threshold"))]
+        threshold: f64,
+        /// Show the operations that would be performed without touching the files or the database.
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+        /// Write the operations and the files needing review to this file, as JSON.
+        #[structopt(long = "report")]
+        report: Option<String>,
+    },
+
+    /// List the movies of the library, optionally filtered.
+    #[structopt(name = "list")]
+    List {
+        /// Part of the title or original title.
+        #[structopt(long = "title")]
+        title: Option<String>,
+        #[structopt(long = "year")]
+        year: Option<i32>,
+        /// Part of a genre's name.
+        #[structopt(long = "genre")]
+        genre: Option<String>,
+        /// Part of a director's name.
+        #[structopt(long = "director")]
+        director: Option<String>,
+        /// Part of the name of one of the main actors.
+        #[structopt(long = "actor")]
+        actor: Option<String>,
+        /// Certification, such as PG-13.
+        #[structopt(long = "certification")]
+        certification: Option<String>,
+        /// Part of the collection's name.
+        #[structopt(long = "collection")]
+        collection: Option<String>,
+        /// ISO 639-1 code of a spoken language, such as en.
+        #[structopt(long = "language")]
+        language: Option<String>,
+        /// Maximum runtime, in minutes.
+        #[structopt(long = "max-runtime")]
+        max_runtime: Option<i32>,
+        /// Language the movie has neither audio nor subtitles in, such as fr.
+        #[structopt(long = "missing-language")]
+        missing_language: Option<String>,
+    },
+
+    /// Download the missing posters and backdrops of the movies in the library.
+    #[structopt(name = "images")]
+    Images {
+        /// Download the images of every movie again.
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    /// Read the tracks, resolution, codecs and duration of the movies of the library.
+    #[structopt(name = "tracks")]
+    Tracks {
+        /// Read the headers of every movie again.
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    /// Write the Kodi NFO files of the movies in the library.
+    #[structopt(name = "nfo")]
+    Nfo {
+        /// Overwrite the existing NFO files.
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    /// Show or change the naming template of a library, `movies` or `tv`.
+    #[structopt(name = "template")]
+    Template {
+        library: String,
+        template: Option<String>,
+    },
+
+    /// Inspect or purge the cache of TMDB responses.
+    #[structopt(name = "cache")]
+    Cache {
+        #[structopt(subcommand)]
+        command: CacheCommands,
+    },
 
     /// Cleanup the database.
     #[structopt(name = "sync")]
@@ -49,6 +166,32 @@ pub enum Commands {
     Test,
 }
 
+#[derive(StructOpt, Debug)]
+pub enum CacheCommands {
+    /// Show the number of cached responses and their size.
+    #[structopt(name = "info")]
+    Info,
+    /// Remove the cached responses.
+    #[structopt(name = "purge")]
+    Purge {
+        /// Only remove the responses that expired.
+        #[structopt(long = "expired")]
+        expired: bool,
+    },
+}
+
+lazy_static! {
+    static ref DEFAULT_THRESHOLD: String = matching::DEFAULT_THRESHOLD.to_string();
+}
+
+fn parse_threshold(value: &str) -> Result<f64, String> {
+    match value.parse() {
+        Ok(threshold) if threshold >= 0.0 && threshold <= 1.0 => Ok(threshold),
+        Ok(_) => Err("the threshold must be between 0 and 1".into()),
+        Err(err) => Err(format!("{}", err)),
+    }
+}
+
 static TEMPLATE: &'static str = "\
 USAGE:
     {usage}
@@ -56,26 +199,129 @@ USAGE:
 {all-args}
 ";
 
+fn tmdb_client(config: &Config, offline: bool) -> tmdb::Client {
+    match tmdb::Client::from_config(config, offline) {
+        Ok(client) => client,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
-    let app = Commands::clap();
+    let app = Args::clap();
     let app = app.template(TEMPLATE);
-    let args = Commands::from_clap(&app.get_matches());
+    let args = Args::from_clap(&app.get_matches());
 
-    let mut database = Database::open("database.json")
+    let mut config = Config::load(args.config).expect("unable to load configuration");
+    let mut database = Database::open(config.database_path())
         .expect("unable to open database")
-        .unwrap_or_else(|| {
-            println!(
-                "Your database has not been initialized. Please answer the following questions:"
-            );
-            let movies_path = input::question_path("Where do you want to store movies?");
-            let tv_path = input::question_path("Where do you want to store tv?");
-            Database::new(movies_path, tv_path)
-        });
-
-    // let args = Commands::from_args();
-    match args {
-        Commands::Import { path } => {
-            tasks::import::import(path, &mut database);
+        .unwrap_or_else(Database::new);
+
+    let mut config_changed = database.migrate_settings(&mut config);
+    if config.movies.path.is_none() || config.tv.path.is_none() {
+        println!(
+            "Your libraries have not been configured. Please answer the following questions:"
+        );
+        if config.movies.path.is_none() {
+            config.movies.path = Some(input::question_path("Where do you want to store movies?"));
+        }
+        if config.tv.path.is_none() {
+            config.tv.path = Some(input::question_path("Where do you want to store tv?"));
+        }
+        config_changed = true;
+    }
+    if config_changed {
+        config.save().expect("unable to save configuration");
+        println!("Configuration saved to {}", config.path().display());
+    }
+
+    let mut save = true;
+
+    match args.command {
+        Commands::Import {
+            path,
+            interactive,
+            unattended,
+            threshold,
+            dry_run,
+            report: report_path,
+        } => {
+            let options = tasks::import::Options {
+                matching: matching::Options {
+                    interactive,
+                    unattended,
+                    threshold,
+                },
+                dry_run,
+            };
+            let client = tmdb_client(&config, args.offline);
+            let result = tasks::import::import(path, &mut database, &config, &client, &options);
+            let report = match result {
+                Ok(report) => report,
+                Err(err) => {
+                    println!("Import failed: {}", err);
+                    process::exit(1);
+                }
+            };
+            if dry_run {
+                tasks::import::print_plan(&report.operations);
+                // The database was updated in memory to plan the operations, do not save it.
+                save = false;
+            }
+            tasks::import::print_review_report(&report.reviews);
+            tasks::import::print_failures(&report.failures);
+            if let Some(report_path) = report_path {
+                let file = File::create(report_path).expect("unable to create report");
+                serde_json::to_writer_pretty(file, &report).expect("unable to write report");
+            }
+        }
+        Commands::List {
+            title,
+            year,
+            genre,
+            director,
+            actor,
+            certification,
+            collection,
+            language,
+            max_runtime,
+            missing_language,
+        } => {
+            let filter = tasks::list::Filter {
+                title,
+                year,
+                genre,
+                director,
+                actor,
+                certification,
+                collection,
+                language,
+                max_runtime,
+                missing_language,
+            };
+            tasks::list::list(&database, &filter);
+        }
+        Commands::Images { force } => {
+            let client = tmdb_client(&config, args.offline);
+            tasks::images::backfill(&mut database, &client, &config, force);
+        }
+        Commands::Tracks { force } => {
+            tasks::tracks::scan(&mut database, force);
+        }
+        Commands::Nfo { force } => {
+            tasks::nfo::regenerate(&database, force);
+        }
+        Commands::Template { library, template } => {
+            tasks::template::template(&mut config, &library, template.as_ref().map(|t| &t[..]));
+        }
+        Commands::Cache { command } => {
+            let cache = tmdb::cache::Cache::new(config.cache_path());
+            match command {
+                CacheCommands::Info => tasks::cache::info(&cache),
+                CacheCommands::Purge { expired } => tasks::cache::purge(&cache, expired),
+            }
         }
         Commands::Sync => {
             tasks::sync::sync(&mut database);
@@ -85,13 +331,16 @@ fn main() {
             println!("{}", hash);
         }
         Commands::Test => {
-            let r = tmdb::search::movie("star wars empire strikes back", None).unwrap();
+            let client = tmdb_client(&config, args.offline);
+            let r = tmdb::search::movie(&client, "star wars empire strikes back", None).unwrap();
             println!("{:#?}", r);
         }
         _ => {}
     }
 
-    database
-        .save("database.json")
-        .expect("unable to save database, this is bad");
+    if save {
+        database
+            .save(config.database_path())
+            .expect("unable to save database, this is bad");
+    }
 }
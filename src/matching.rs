@@ -0,0 +1,464 @@
+use std::cmp::{self, Ordering};
+use std::path::{Path, PathBuf};
+
+use error;
+use input::{self, Choice};
+use parse;
+use parse::ids::ExternalId;
+use provider::MetadataProvider;
+use tmdb::search;
+
+/// Number of candidates shown to the user in interactive mode.
+const CANDIDATES: usize = 5;
+/// Length of the overview snippet shown to the user in interactive mode.
+const SNIPPET_LEN: usize = 100;
+/// Default minimum score for a candidate to be accepted without asking.
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// A search result that can be matched to a file.
+pub trait Candidate: Sized {
+    fn search(
+        provider: &MetadataProvider,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<Self>, error::Error>;
+    fn get(provider: &MetadataProvider, id: i64) -> Result<Self, error::Error>;
+    /// Find the candidate with the external id, if there is one.
+    fn find(provider: &MetadataProvider, id: &ExternalId) -> Result<Option<Self>, error::Error>;
+
+    fn title(&self) -> &str;
+    fn original_title(&self) -> &str;
+    fn date(&self) -> &str;
+    fn overview(&self) -> &str;
+    fn popularity(&self) -> f64;
+}
+
+impl Candidate for search::Movie {
+    fn search(
+        provider: &MetadataProvider,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<Self>, error::Error> {
+        provider.search_movie(query, year)
+    }
+
+    fn get(provider: &MetadataProvider, id: i64) -> Result<Self, error::Error> {
+        provider.movie(id)
+    }
+
+    fn find(provider: &MetadataProvider, id: &ExternalId) -> Result<Option<Self>, error::Error> {
+        match *id {
+            ExternalId::Tmdb(id) => provider.movie(id).map(Some),
+            ExternalId::Imdb(ref id) => {
+                Ok(provider.find_imdb(id)?.movie_results.into_iter().next())
+            }
+        }
+    }
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn original_title(&self) -> &str {
+        &self.original_title
+    }
+
+    fn date(&self) -> &str {
+        &self.release_date
+    }
+
+    fn overview(&self) -> &str {
+        &self.overview
+    }
+
+    fn popularity(&self) -> f64 {
+        self.popularity
+    }
+}
+
+impl Candidate for search::Show {
+    fn search(
+        provider: &MetadataProvider,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<Self>, error::Error> {
+        provider.search_show(query, year)
+    }
+
+    fn get(provider: &MetadataProvider, id: i64) -> Result<Self, error::Error> {
+        provider.show(id)
+    }
+
+    /// IMDb ids in episode filenames are often the ids of the episodes, the show is then found
+    /// through the episode.
+    fn find(provider: &MetadataProvider, id: &ExternalId) -> Result<Option<Self>, error::Error> {
+        match *id {
+            ExternalId::Tmdb(id) => provider.show(id).map(Some),
+            ExternalId::Imdb(ref id) => {
+                let results = provider.find_imdb(id)?;
+                match results.tv_results.into_iter().next() {
+                    Some(show) => Ok(Some(show)),
+                    None => match results.tv_episode_results.first() {
+                        Some(episode) => provider.show(episode.show_id).map(Some),
+                        None => Ok(None),
+                    },
+                }
+            }
+        }
+    }
+
+    fn title(&self) -> &str {
+        &self.name
+    }
+
+    fn original_title(&self) -> &str {
+        &self.original_name
+    }
+
+    fn date(&self) -> &str {
+        &self.first_air_date
+    }
+
+    fn overview(&self) -> &str {
+        &self.overview
+    }
+
+    fn popularity(&self) -> f64 {
+        self.popularity
+    }
+}
+
+/// Options used to pick a candidate among the search results.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Ask the user to choose when the best candidate's score is below the threshold.
+    pub interactive: bool,
+    /// Never ask the user, candidates below the threshold are left for review.
+    pub unattended: bool,
+    /// Minimum score, between 0 and 1, for a candidate to be accepted without asking.
+    pub threshold: f64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            interactive: false,
+            unattended: false,
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+}
+
+/// Result of the matching of a file.
+#[derive(Debug)]
+pub enum Outcome<C> {
+    Matched(C),
+    /// The user chose to skip the file.
+    Skipped,
+    /// No candidate scored high enough to be accepted in unattended mode.
+    Review(Review),
+}
+
+/// A file that could not be matched with enough confidence.
+#[derive(Debug, Clone, Serialize)]
+pub struct Review {
+    pub path: PathBuf,
+    pub query: String,
+    pub year: Option<i32>,
+    /// Title and year of the best candidate, if there was any.
+    pub best: Option<String>,
+    pub score: f64,
+}
+
+/// Lowercase the text and replace punctuation with single spaces.
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let next = cmp::min(cmp::min(row[j + 1] + 1, row[j] + 1), prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// Similarity of two strings, once normalized, between 0 and 1.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a = normalize(a);
+    let b = normalize(b);
+    let len = cmp::max(a.chars().count(), b.chars().count());
+    if len == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / len as f64
+}
+
+fn candidate_year<C: Candidate>(candidate: &C) -> Option<i32> {
+    candidate.date().get(..4).and_then(|y| y.parse().ok())
+}
+
+/// Score a candidate against the title and year parsed from the filename, between 0 and 1.
+///
+/// The title is compared to the candidate's title and original title, the best similarity is kept.
+/// Release dates often differ by a year between countries, so a year off by one still gets half
+/// the points. Popularity is relative to the most popular candidate of the search and only breaks
+/// ties between similar titles.
+pub fn score<C>(candidate: &C, query: &str, year: Option<i32>, max_popularity: f64) -> f64
+where
+    C: Candidate,
+{
+    let title = f64::max(
+        similarity(query, candidate.title()),
+        similarity(query, candidate.original_title()),
+    );
+    let popularity = if max_popularity > 0.0 {
+        candidate.popularity() / max_popularity
+    } else {
+        0.0
+    };
+
+    match year {
+        Some(year) => {
+            let year_score = match candidate_year(candidate).map(|y| (y - year).abs()) {
+                Some(0) => 1.0,
+                Some(1) => 0.5,
+                _ => 0.0,
+            };
+            0.7 * title + 0.2 * year_score + 0.1 * popularity
+        }
+        None => 0.9 * title + 0.1 * popularity,
+    }
+}
+
+/// Sort the candidates by descending score, returning the scores.
+fn rank<C: Candidate>(results: &mut Vec<C>, query: &str, year: Option<i32>) -> Vec<f64> {
+    let max_popularity = results.iter().map(|c| c.popularity()).fold(0.0, f64::max);
+    let mut scored: Vec<(f64, C)> = results
+        .drain(..)
+        .map(|c| (score(&c, query, year, max_popularity), c))
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let mut scores = vec![];
+    for (score, candidate) in scored {
+        scores.push(score);
+        results.push(candidate);
+    }
+    scores
+}
+
+/// Shorten the text to roughly `len` characters, on a word boundary.
+fn snippet(text: &str, len: usize) -> String {
+    if text.chars().count() <= len {
+        return text.into();
+    }
+    let mut snippet = String::new();
+    for word in text.split_whitespace() {
+        if snippet.chars().count() + word.chars().count() > len {
+            break;
+        }
+        if !snippet.is_empty() {
+            snippet.push(' ');
+        }
+        snippet.push_str(word);
+    }
+    snippet.push_str("...");
+    snippet
+}
+
+fn describe<C: Candidate>(candidate: &C) -> String {
+    let year = candidate.date().get(..4).unwrap_or("????");
+    if candidate.original_title() != candidate.title() {
+        format!(
+            "{} ({}) [{}]",
+            candidate.title(),
+            year,
+            candidate.original_title()
+        )
+    } else {
+        format!("{} ({})", candidate.title(), year)
+    }
+}
+
+fn print_candidates<C: Candidate>(results: &[C], scores: &[f64]) {
+    if results.is_empty() {
+        println!("    No results.");
+    }
+    for (idx, (candidate, score)) in results.iter().zip(scores).enumerate() {
+        println!(
+            "    {}) {} {:.0}%",
+            idx + 1,
+            describe(candidate),
+            score * 100.0
+        );
+        if !candidate.overview().is_empty() {
+            println!("       {}", snippet(candidate.overview(), SNIPPET_LEN));
+        }
+    }
+}
+
+/// Choose the candidate with the external id found in the file's name or NFO file. The search is
+/// only used when there is no id or when nothing has that id.
+pub fn identify<C>(
+    provider: &MetadataProvider,
+    path: &Path,
+    id: Option<&ExternalId>,
+    query: &str,
+    year: Option<i32>,
+    options: &Options,
+) -> Result<Outcome<C>, error::Error>
+where
+    C: Candidate,
+{
+    if let Some(id) = id {
+        match C::find(provider, id) {
+            Ok(Some(candidate)) => return Ok(Outcome::Matched(candidate)),
+            Ok(None) => println!("Nothing was found with id {}, searching instead.", id),
+            Err(err) => println!("Unable to find id {} ({}), searching instead.", id, err),
+        }
+    }
+    choose(provider, path, query, year, options)
+}
+
+/// Search for the query and choose a candidate among the results.
+///
+/// Candidates are ranked by `score`. By default, the best candidate is chosen. In unattended
+/// mode, the best candidate is only chosen if its score reaches the threshold, otherwise the file
+/// is left for review. In interactive mode, the user is asked to choose when the best score is
+/// below the threshold. The user can then type a new search, enter the id directly or skip the
+/// file. Fails if the search fails.
+pub fn choose<C>(
+    provider: &MetadataProvider,
+    path: &Path,
+    query: &str,
+    year: Option<i32>,
+    options: &Options,
+) -> Result<Outcome<C>, error::Error>
+where
+    C: Candidate,
+{
+    let mut query = query.to_owned();
+    let mut year = year;
+    let mut confirm = false;
+
+    loop {
+        let mut results = C::search(provider, &query, year)?;
+        let scores = rank(&mut results, &query, year);
+        let best_score = scores.first().cloned().unwrap_or(0.0);
+
+        if !confirm && (best_score >= options.threshold || !options.interactive) {
+            if results.is_empty() {
+                return Ok(Outcome::Review(Review {
+                    path: path.to_owned(),
+                    query,
+                    year,
+                    best: None,
+                    score: 0.0,
+                }));
+            }
+            if best_score >= options.threshold || !options.unattended {
+                return Ok(Outcome::Matched(results.remove(0)));
+            }
+            return Ok(Outcome::Review(Review {
+                path: path.to_owned(),
+                query,
+                year,
+                best: Some(describe(&results[0])),
+                score: best_score,
+            }));
+        }
+
+        results.truncate(CANDIDATES);
+        println!("Search results for {}:", path.display());
+        print_candidates(&results, &scores);
+
+        match input::question_choice("Which one is it?", results.len()) {
+            Choice::Pick(idx) => return Ok(Outcome::Matched(results.remove(idx))),
+            Choice::Skip => return Ok(Outcome::Skipped),
+            Choice::Query(text) => {
+                let (new_query, new_year) = parse::movie::parse_movie(&text);
+                query = new_query;
+                year = new_year;
+                // A new search is always confirmed by the user.
+                confirm = true;
+            }
+            Choice::Id(id) => match C::get(provider, id) {
+                Ok(candidate) => return Ok(Outcome::Matched(candidate)),
+                Err(_) => println!("Nothing was found with id {}.", id),
+            },
+        }
+    }
+}
+
+#[test]
+fn test_snippet() {
+    assert_eq!(snippet("short", 10), "short");
+    assert_eq!(
+        snippet("a computer hacker learns about the true nature", 20),
+        "a computer hacker..."
+    );
+}
+
+#[test]
+fn test_similarity() {
+    assert_eq!(similarity("The Matrix", "the matrix"), 1.0);
+    assert_eq!(similarity("Snatch!", "snatch"), 1.0);
+    assert!(similarity("the matrix", "the matrix reloaded") < 0.6);
+    assert_eq!(similarity("", ""), 1.0);
+}
+
+#[cfg(test)]
+fn movie(title: &str, original_title: &str, date: &str, popularity: f64) -> search::Movie {
+    search::Movie {
+        id: 1,
+        title: title.into(),
+        original_title: original_title.into(),
+        overview: String::new(),
+        release_date: date.into(),
+        poster_path: None,
+        backdrop_path: None,
+        popularity,
+    }
+}
+
+#[test]
+fn test_score() {
+    let exact = movie("The Matrix", "The Matrix", "1999-03-30", 10.0);
+    assert!(score(&exact, "the matrix", Some(1999), 10.0) > 0.99);
+
+    let off_by_one = movie("The Matrix", "The Matrix", "2000-03-30", 10.0);
+    assert!(score(&off_by_one, "the matrix", Some(1999), 10.0) < 0.95);
+    assert!(score(&off_by_one, "the matrix", Some(1999), 10.0) > DEFAULT_THRESHOLD);
+
+    let original = movie("The Untouchables", "Intouchables", "2011-11-02", 5.0);
+    assert!(score(&original, "intouchables", Some(2011), 10.0) > DEFAULT_THRESHOLD);
+
+    let sequel = movie("The Matrix Reloaded", "The Matrix Reloaded", "2003-05-15", 10.0);
+    assert!(score(&sequel, "the matrix", Some(1999), 10.0) < DEFAULT_THRESHOLD);
+}
+
+#[test]
+fn test_rank() {
+    let mut results = vec![
+        movie("The Matrix Reloaded", "The Matrix Reloaded", "2003-05-15", 10.0),
+        movie("The Matrix", "The Matrix", "1999-03-30", 8.0),
+    ];
+    let scores = rank(&mut results, "the matrix", Some(1999));
+    assert_eq!(results[0].title, "The Matrix");
+    assert!(scores[0] > scores[1]);
+}
@@ -0,0 +1,409 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use database::{ImageKind, Movie};
+use error;
+use fs::only_video;
+use parse::ids::{self, ExternalId};
+
+/// Name of the NFO file written next to the movies alone in their directory.
+pub static MOVIE_NFO: &'static str = "movie.nfo";
+
+/// Escape the text for use in XML elements and attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn element(xml: &mut String, name: &str, value: &str) {
+    if !value.is_empty() {
+        let _ = writeln!(xml, "    <{}>{}</{}>", name, escape(value), name);
+    }
+}
+
+/// Render the movie as a Kodi NFO file, which Jellyfin and Emby also read.
+///
+/// Images are referred to by their file name, they are in the same directory as the NFO file.
+pub fn movie(movie: &Movie) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    xml.push_str("<movie>\n");
+    element(&mut xml, "title", &movie.title);
+    element(&mut xml, "originaltitle", &movie.original_title);
+    element(&mut xml, "year", &movie.year.to_string());
+    element(&mut xml, "plot", &movie.overview);
+    element(&mut xml, "tagline", &movie.tagline);
+    if let Some(runtime) = movie.runtime {
+        element(&mut xml, "runtime", &runtime.to_string());
+    }
+    if let Some(ref certification) = movie.certification {
+        element(&mut xml, "mpaa", certification);
+    }
+    let _ = writeln!(
+        xml,
+        "    <uniqueid type=\"tmdb\" default=\"true\">{}</uniqueid>",
+        movie.tmdb_id
+    );
+    if let Some(ref imdb_id) = movie.imdb_id {
+        let _ = writeln!(xml, "    <uniqueid type=\"imdb\">{}</uniqueid>", escape(imdb_id));
+    }
+    for genre in &movie.genres {
+        element(&mut xml, "genre", genre);
+    }
+    if let Some(ref collection) = movie.collection {
+        let _ = writeln!(xml, "    <set>\n        <name>{}</name>\n    </set>", escape(collection));
+    }
+    for director in &movie.directors {
+        element(&mut xml, "director", director);
+    }
+    for (order, actor) in movie.cast.iter().enumerate() {
+        let _ = writeln!(
+            xml,
+            "    <actor>\n        <name>{}</name>\n        <order>{}</order>\n    </actor>",
+            escape(actor),
+            order
+        );
+    }
+    for image in &movie.images {
+        let name = match image.path.file_name() {
+            Some(name) => escape(&name.to_string_lossy()),
+            None => continue,
+        };
+        match image.kind {
+            ImageKind::Poster => {
+                let _ = writeln!(xml, "    <thumb aspect=\"poster\">{}</thumb>", name);
+            }
+            ImageKind::Backdrop => {
+                let _ = writeln!(
+                    xml,
+                    "    <fanart>\n        <thumb>{}</thumb>\n    </fanart>",
+                    name
+                );
+            }
+        }
+    }
+    xml.push_str("</movie>\n");
+    xml
+}
+
+/// Path of the NFO file of the video, `movie.nfo` if it is alone in its directory, otherwise the
+/// name of the video as in `Movie.nfo`.
+pub fn movie_path(video: &Path) -> PathBuf {
+    if only_video(video) {
+        video.with_file_name(MOVIE_NFO)
+    } else {
+        video.with_extension("nfo")
+    }
+}
+
+/// Write the NFO file of the movie in its directory, returning its path.
+pub fn write_movie(movie: &Movie) -> Result<PathBuf, error::Error> {
+    let dir = movie.path.parent().expect("movie path has no directory");
+    fs::create_dir_all(dir)?;
+    let path = movie_path(&movie.path);
+    File::create(&path)?.write_all(self::movie(movie).as_bytes())?;
+    Ok(path)
+}
+
+/// What an existing NFO file says about its video.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Info {
+    pub id: Option<ExternalId>,
+    /// Title and year, only for movies. The title of an episode is not the title of its show.
+    pub title: Option<String>,
+    pub year: Option<i32>,
+}
+
+/// Replace the entities of the XML text, and remove the CDATA markers.
+pub fn unescape(text: &str) -> String {
+    let text = text.trim();
+    if text.starts_with("<![CDATA[") && text.ends_with("]]>") {
+        return text[9..text.len() - 3].trim().into();
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        unescaped.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&' as u32),
+            "lt" => Some('<' as u32),
+            "gt" => Some('>' as u32),
+            "quot" => Some('"' as u32),
+            "apos" => Some('\'' as u32),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok(),
+            _ if entity.starts_with('#') => entity[1..].parse().ok(),
+            _ => None,
+        }.and_then(::std::char::from_u32);
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Name of the root element, such as `movie` or `episodedetails`.
+fn root(text: &str) -> Option<&str> {
+    let mut rest = text;
+    while let Some(pos) = rest.find('<') {
+        rest = &rest[pos + 1..];
+        if !rest.starts_with('?') && !rest.starts_with('!') {
+            let end = rest.find(|c: char| c == '>' || c == '/' || c.is_whitespace())?;
+            return Some(&rest[..end]);
+        }
+    }
+    None
+}
+
+/// Attributes and text of the elements with the given name, in order. Nested elements are not
+/// supported, they are not needed for the elements that are read.
+fn elements<'a>(text: &'a str, name: &str) -> Vec<(&'a str, String)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = vec![];
+    let mut rest = text;
+    while let Some(pos) = rest.find(&open) {
+        rest = &rest[pos + open.len()..];
+        // Only `<name>` and `<name attr="...">`, not `<namesomething>`.
+        if !rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let attrs = &rest[..end];
+        rest = &rest[end + 1..];
+        if attrs.ends_with('/') {
+            found.push((&attrs[..attrs.len() - 1], String::new()));
+            continue;
+        }
+        let end = match rest.find(&close) {
+            Some(end) => end,
+            None => break,
+        };
+        found.push((attrs, unescape(&rest[..end])));
+        rest = &rest[end + close.len()..];
+    }
+    found
+}
+
+/// Value of the attribute, in single or double quotes.
+fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        rest = rest[pos + name.len()..].trim_left();
+        if before.map_or(false, |c| !c.is_whitespace()) || !rest.starts_with('=') {
+            continue;
+        }
+        rest = rest[1..].trim_left();
+        let quote = rest.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let end = rest[1..].find(quote)?;
+        return Some(&rest[1..end + 1]);
+    }
+    None
+}
+
+fn parse_id(kind: &str, value: &str) -> Option<ExternalId> {
+    let value = value.trim();
+    match kind {
+        "tmdb" => value.parse().ok().map(ExternalId::Tmdb),
+        "imdb" if ids::is_imdb_id(value) => Some(ExternalId::Imdb(value.into())),
+        _ => None,
+    }
+}
+
+/// Parse a Kodi NFO file. Plex and others also write NFO files that only contain a link to the
+/// IMDb or TMDB page, the id is then found in the text.
+///
+/// The `uniqueid` marked as default is preferred, then TMDB ids, then IMDb ids. The TMDB ids of
+/// episodes are ignored, they cannot be used to find the show.
+pub fn parse(text: &str) -> Info {
+    let root = root(text).unwrap_or("");
+    let episode = root == "episodedetails";
+    let mut candidates: Vec<(bool, ExternalId)> = vec![];
+    for (attrs, value) in elements(text, "uniqueid") {
+        let kind = attribute(attrs, "type").unwrap_or("").to_lowercase();
+        if let Some(id) = parse_id(&kind, &value) {
+            candidates.push((attribute(attrs, "default") == Some("true"), id));
+        }
+    }
+    // Older files have dedicated elements, `id` is the IMDb id.
+    for &(name, kind) in &[("tmdbid", "tmdb"), ("imdbid", "imdb"), ("id", "imdb")] {
+        for (_, value) in elements(text, name) {
+            if let Some(id) = parse_id(kind, &value) {
+                candidates.push((false, id));
+            }
+        }
+    }
+    if episode {
+        candidates.retain(|&(_, ref id)| match *id {
+            ExternalId::Imdb(_) => true,
+            ExternalId::Tmdb(_) => false,
+        });
+    }
+    candidates.sort_by_key(|&(default, ref id)| match (default, id) {
+        (true, _) => 0,
+        (false, &ExternalId::Tmdb(_)) => 1,
+        (false, &ExternalId::Imdb(_)) => 2,
+    });
+    let id = match candidates.into_iter().next() {
+        Some((_, id)) => Some(id),
+        None if root == "" || root == "movie" || root == "tvshow" => ids::find_in_text(text),
+        None => None,
+    };
+
+    let mut info = Info {
+        id,
+        ..Info::default()
+    };
+    if root == "movie" {
+        info.title = elements(text, "title")
+            .into_iter()
+            .map(|(_, title)| title)
+            .find(|title| !title.is_empty());
+        // The year is sometimes only in the release date, such as `1999-03-30`.
+        info.year = elements(text, "year")
+            .into_iter()
+            .chain(elements(text, "premiered"))
+            .chain(elements(text, "releasedate"))
+            .filter_map(|(_, date)| date.get(..4).and_then(|year| year.parse().ok()))
+            .next();
+    }
+    info
+}
+
+/// Read and parse a NFO file. They are not always UTF-8, the invalid characters are replaced.
+pub fn read(path: &Path) -> Result<Info, error::Error> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(parse(&String::from_utf8_lossy(&bytes)))
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(escape("Tom & Jerry <3 \"x\""), "Tom &amp; Jerry &lt;3 &quot;x&quot;");
+}
+
+#[test]
+fn test_movie() {
+    use database::Image;
+
+    let movie = Movie {
+        tmdb_id: 603,
+        title: "The Matrix".into(),
+        original_title: "The Matrix".into(),
+        year: 1999,
+        overview: "Neo & the red pill.".into(),
+        imdb_id: Some("tt0133093".into()),
+        genres: vec!["Action".into()],
+        cast: vec!["Keanu Reeves".into()],
+        path: "/movies/The Matrix (1999)/The Matrix (1999).mkv".into(),
+        images: vec![
+            Image {
+                kind: ImageKind::Poster,
+                path: "/movies/The Matrix (1999)/poster.jpg".into(),
+            },
+        ],
+        ..Movie::default()
+    };
+    let xml = self::movie(&movie);
+    assert!(xml.contains("<title>The Matrix</title>"));
+    assert!(xml.contains("<year>1999</year>"));
+    assert!(xml.contains("<plot>Neo &amp; the red pill.</plot>"));
+    assert!(xml.contains("<uniqueid type=\"tmdb\" default=\"true\">603</uniqueid>"));
+    assert!(xml.contains("<uniqueid type=\"imdb\">tt0133093</uniqueid>"));
+    assert!(xml.contains("<name>Keanu Reeves</name>"));
+    assert!(xml.contains("<thumb aspect=\"poster\">poster.jpg</thumb>"));
+    assert!(!xml.contains("<tagline>"));
+}
+
+#[test]
+fn test_parse() {
+    let movie = Movie {
+        tmdb_id: 603,
+        title: "Tom & Jerry".into(),
+        year: 1999,
+        imdb_id: Some("tt0133093".into()),
+        ..Movie::default()
+    };
+    assert_eq!(
+        parse(&self::movie(&movie)),
+        Info {
+            id: Some(ExternalId::Tmdb(603)),
+            title: Some("Tom & Jerry".into()),
+            year: Some(1999),
+        }
+    );
+
+    let legacy = "<?xml version='1.0'?>\n<movie>\n  <title><![CDATA[The Matrix]]></title>\n  \
+                  <premiered>1999-03-30</premiered>\n  <id>tt0133093</id>\n</movie>";
+    assert_eq!(
+        parse(legacy),
+        Info {
+            id: Some(ExternalId::Imdb("tt0133093".into())),
+            title: Some("The Matrix".into()),
+            year: Some(1999),
+        }
+    );
+
+    let episode = "<episodedetails><title>Serenity</title>\
+                   <uniqueid type=\"tmdb\" default=\"true\">63080</uniqueid>\
+                   <uniqueid type=\"imdb\">tt0579539</uniqueid></episodedetails>";
+    assert_eq!(
+        parse(episode),
+        Info {
+            id: Some(ExternalId::Imdb("tt0579539".into())),
+            ..Info::default()
+        }
+    );
+
+    assert_eq!(
+        parse("https://www.themoviedb.org/movie/603-the-matrix"),
+        Info {
+            id: Some(ExternalId::Tmdb(603)),
+            ..Info::default()
+        }
+    );
+}
+
+#[test]
+fn test_movie_path() {
+    let dir = ::std::env::temp_dir().join("merovingian-test-nfo");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let matrix = dir.join("The Matrix (1999).mkv");
+    assert_eq!(movie_path(&matrix), dir.join("movie.nfo"));
+    File::create(dir.join("Heat (1995).mkv")).unwrap();
+    assert_eq!(movie_path(&matrix), dir.join("The Matrix (1999).nfo"));
+    fs::remove_dir_all(&dir).unwrap();
+}
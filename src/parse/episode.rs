@@ -0,0 +1,520 @@
+use super::metadata;
+use super::release;
+use super::{is_year, parse_filename, Scope, Token};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EpisodeInfo {
+    pub show: String,
+    pub year: Option<i32>,
+    pub season: Option<i32>, // None when the episodes use absolute numbering.
+    pub episodes: Vec<i32>,
+    pub title: Option<String>,
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_digit(10))
+}
+
+/// Parse a number with an optional anime style version suffix, such as `07v2`.
+fn parse_versioned_number(text: &str) -> Option<i32> {
+    let number = match text.find('v') {
+        Some(pos) if is_number(&text[pos + 1..]) => &text[..pos],
+        _ => text,
+    };
+    if is_number(number) && number.len() <= 4 {
+        number.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse a `s01e02`, `s01e02e03` or `s01` style marker.
+fn parse_season_episode(token: &str) -> Option<(i32, Vec<i32>)> {
+    if !token.starts_with('s') {
+        return None;
+    }
+    let mut parts = token[1..].split('e');
+    let season = parts.next()?;
+    if !is_number(season) {
+        return None;
+    }
+    let mut episodes = vec![];
+    for part in parts {
+        if !is_number(part) {
+            return None;
+        }
+        episodes.push(part.parse().ok()?);
+    }
+    if episodes.is_empty() {
+        return None;
+    }
+    Some((season.parse().ok()?, episodes))
+}
+
+/// Parse a `1x02` style marker.
+fn parse_cross(token: &str) -> Option<(i32, Vec<i32>)> {
+    let x_pos = token.find('x')?;
+    let season = &token[..x_pos];
+    let episode = &token[x_pos + 1..];
+    if is_number(season) && season.len() <= 2 && is_number(episode) && episode.len() <= 3 {
+        Some((season.parse().ok()?, vec![episode.parse().ok()?]))
+    } else {
+        None
+    }
+}
+
+/// Parse a `season 1 episode 2` marker, spread over four tokens.
+fn parse_words(tokens: &[Token]) -> Option<(i32, Vec<i32>)> {
+    if tokens.len() < 4 || tokens[0].text != "season" || tokens[2].text != "episode" {
+        return None;
+    }
+    if is_number(tokens[1].text) && is_number(tokens[3].text) {
+        Some((tokens[1].parse().ok()?, vec![tokens[3].parse().ok()?]))
+    } else {
+        None
+    }
+}
+
+/// Find the season and episode marker. Returns the index of the marker, the number of tokens it
+/// spans, the season and the episodes.
+fn find_marker(tokens: &[Token]) -> Option<(usize, usize, i32, Vec<i32>)> {
+    for (idx, token) in tokens.iter().enumerate() {
+        if let Some((season, episodes)) = parse_season_episode(token).or_else(|| parse_cross(token))
+        {
+            return Some((idx, 1, season, episodes));
+        }
+        if let Some((season, episodes)) = parse_words(&tokens[idx..]) {
+            return Some((idx, 4, season, episodes));
+        }
+    }
+    None
+}
+
+/// Largest range of episodes a single file may span, `s01e01-e99999` is not a range.
+const MAX_EPISODE_SPAN: i32 = 30;
+
+/// Text between two consecutive tokens of the filename they were split from.
+fn separator<'a>(filename: &'a str, before: &Token, after: &Token) -> &'a str {
+    let base = filename.as_ptr() as usize;
+    let start = before.text.as_ptr() as usize - base + before.len();
+    let end = after.text.as_ptr() as usize - base;
+    &filename[start..end]
+}
+
+/// Extend the episodes of a marker with a range or a list of episodes following it, such as
+/// `s01e01-e03` or `s01e01-02`. `start` is the index of the token after the marker in the tokens
+/// of `filename`. Bare numbers must follow a dash, `s01e02.24.hours` is a single episode. Returns
+/// the number of tokens consumed.
+fn parse_continuation(
+    filename: &str,
+    tokens: &[Token],
+    start: usize,
+    season: i32,
+    episodes: &mut Vec<i32>,
+) -> usize {
+    let mut consumed = 0;
+    for idx in start..tokens.len() {
+        let token = &tokens[idx];
+        let last = episodes[episodes.len() - 1];
+        let dash = idx > 0 && separator(filename, &tokens[idx - 1], token).contains('-');
+        let next = if token.starts_with('e') && is_number(&token[1..]) && token.len() <= 4 {
+            token[1..].parse().ok()
+        } else if is_number(token) && token.len() <= 3 {
+            if dash {
+                token.parse().ok()
+            } else {
+                None
+            }
+        } else {
+            match parse_season_episode(token).or_else(|| parse_cross(token)) {
+                Some((s, ref eps)) if s == season && eps[eps.len() - 1] < 1000 => {
+                    Some(eps[eps.len() - 1])
+                }
+                _ => None,
+            }
+        };
+        match next {
+            Some(next) if next > last && next - last <= MAX_EPISODE_SPAN => {
+                episodes.extend(last + 1..next + 1);
+                consumed += 1;
+            }
+            _ => break,
+        }
+    }
+    consumed
+}
+
+/// Find an absolute episode number, used by anime releases such as
+/// `[Group] Show Name - 12 [1080p]`. Only files starting with a release group in square brackets
+/// are considered, otherwise titles such as 'Apollo 13' would be mistaken for episodes.
+fn find_absolute(tokens: &[Token], first_word_index: usize) -> Option<(usize, i32)> {
+    if tokens.first().map(|t| t.scope) != Some(Scope::Square) {
+        return None;
+    }
+    for (idx, token) in tokens.iter().enumerate().skip(first_word_index + 1) {
+        if token.scope != Scope::Normal {
+            continue;
+        }
+        let text = if token.starts_with("ep") {
+            &token[2..]
+        } else if token.starts_with('e') {
+            &token[1..]
+        } else {
+            token.text
+        };
+        if is_year(text) {
+            continue;
+        }
+        if let Some(number) = parse_versioned_number(text) {
+            return Some((idx, number));
+        }
+    }
+    None
+}
+
+fn join(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|t| t.text)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Try to extract the show's name, the season, the episode numbers, the year and the episode's
+/// title from a filename.
+///
+/// The season and episode are expected to be written as `S01E02`, `1x02` or `Season 1 Episode 2`.
+/// Multi-episode files are written as `S01E01E02`, `S01E01-E03` or `S01E01-03`. Anime releases
+/// which start with a release group in square brackets may use absolute numbering instead, such as
+/// `[Group] Show - 12`, in which case the season is `None`.
+///
+/// The show's name is everything before the marker, ignoring release names in square brackets or
+/// parens before the first normal word. A year right before the marker is assumed to be the show's
+/// first air date. The episode's title is everything after the marker until the first metadata
+/// token, bracketed token or file extension.
+///
+/// Returns `None` if no episode marker is found, in which case the file is probably a movie.
+pub fn parse_episode(filename: &str) -> Option<EpisodeInfo> {
+    let filename = filename.to_lowercase();
+    let tokens = parse_filename(&filename);
+
+    let first_word_index = tokens
+        .iter()
+        .position(|t| t.scope == Scope::Normal)
+        .unwrap_or(0);
+
+    let (marker_idx, title_idx, season, episodes) = match find_marker(&tokens) {
+        Some((idx, len, season, mut episodes)) => {
+            let consumed = parse_continuation(&filename, &tokens, idx + len, season, &mut episodes);
+            (idx, idx + len + consumed, Some(season), episodes)
+        }
+        None => {
+            let (idx, number) = find_absolute(&tokens, first_word_index)?;
+            (idx, idx + 1, None, vec![number])
+        }
+    };
+
+    let end = (0..marker_idx)
+        .position(|idx| release::is_metadata(&tokens[idx..]))
+        .unwrap_or(marker_idx);
+
+    let mut show_tokens = if first_word_index < end {
+        &tokens[first_word_index..end]
+    } else {
+        &tokens[..0]
+    };
+    let mut year = None;
+
+    if show_tokens.len() > 1 && is_year(show_tokens[show_tokens.len() - 1].text) {
+        year = show_tokens[show_tokens.len() - 1].parse().ok();
+        show_tokens = &show_tokens[..show_tokens.len() - 1];
+    }
+
+    let title_tokens = &tokens[title_idx..];
+    let title_end = (0..title_tokens.len())
+        .position(|idx| {
+            let t = &title_tokens[idx];
+            t.scope != Scope::Normal || release::is_metadata(&title_tokens[idx..])
+                || metadata::VIDEO_FILES.contains(&t.text)
+        })
+        .unwrap_or(title_tokens.len());
+    let title = join(&title_tokens[..title_end]);
+
+    Some(EpisodeInfo {
+        show: join(show_tokens),
+        year,
+        season,
+        episodes,
+        title: if title.is_empty() { None } else { Some(title) },
+    })
+}
+
+/// Try to extract a season number from a folder's name, such as `Season 2`, `Saison 2`,
+/// `Show.S02.1080p` or `Specials`, which is season 0.
+pub fn parse_season_folder(name: &str) -> Option<i32> {
+    let name = name.to_lowercase();
+    let tokens = parse_filename(&name);
+
+    if tokens.len() == 1 && tokens[0].text == "specials" {
+        return Some(0);
+    }
+
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.text {
+            "season" | "saison" | "series" => {
+                if let Some(next) = tokens.get(idx + 1) {
+                    if is_number(next) && next.len() <= 2 {
+                        return next.parse().ok();
+                    }
+                }
+            }
+            text if text.starts_with('s') && is_number(&text[1..]) && text.len() <= 3 => {
+                return text[1..].parse().ok();
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Try to extract the episode number from a filename, when the season is known from somewhere
+/// else, such as the folder containing the file.
+///
+/// Filenames with a full marker are parsed like `parse_episode`. Otherwise the episode number is
+/// the first number of the filename, or a number prefixed by `e`, `ep` or `episode`, as in
+/// `Season 2/02 - Title.mkv`. The show's name is whatever comes before the number, usually nothing.
+pub fn parse_episode_in_season(filename: &str, season: i32) -> Option<EpisodeInfo> {
+    if let Some(mut info) = parse_episode(filename) {
+        info.season = info.season.or(Some(season));
+        return Some(info);
+    }
+
+    let filename = filename.to_lowercase();
+    let tokens = parse_filename(&filename);
+
+    let mut marker = None;
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.scope != Scope::Normal || is_year(token) {
+            continue;
+        }
+        if token.text == "episode" {
+            if let Some(next) = tokens.get(idx + 1) {
+                if let Some(number) = parse_versioned_number(next) {
+                    marker = Some((idx, 2, number));
+                    break;
+                }
+            }
+        }
+        let text = if token.starts_with("ep") {
+            &token[2..]
+        } else if token.starts_with('e') {
+            &token[1..]
+        } else {
+            token.text
+        };
+        if text.len() <= 3 {
+            if let Some(number) = parse_versioned_number(text) {
+                marker = Some((idx, 1, number));
+                break;
+            }
+        }
+    }
+
+    let (marker_idx, len, number) = marker?;
+    let mut episodes = vec![number];
+    let consumed =
+        parse_continuation(&filename, &tokens, marker_idx + len, season, &mut episodes);
+
+    let first_word_index = tokens
+        .iter()
+        .position(|t| t.scope == Scope::Normal)
+        .unwrap_or(0);
+    let show_tokens = if first_word_index < marker_idx {
+        &tokens[first_word_index..marker_idx]
+    } else {
+        &tokens[..0]
+    };
+
+    let title_tokens = &tokens[marker_idx + len + consumed..];
+    let title_end = (0..title_tokens.len())
+        .position(|idx| {
+            let t = &title_tokens[idx];
+            t.scope != Scope::Normal || release::is_metadata(&title_tokens[idx..])
+                || metadata::VIDEO_FILES.contains(&t.text)
+        })
+        .unwrap_or(title_tokens.len());
+    let title = join(&title_tokens[..title_end]);
+
+    Some(EpisodeInfo {
+        show: join(show_tokens),
+        year: None,
+        season: Some(season),
+        episodes,
+        title: if title.is_empty() { None } else { Some(title) },
+    })
+}
+
+#[cfg(test)]
+fn info(
+    show: &str,
+    year: Option<i32>,
+    season: Option<i32>,
+    episodes: Vec<i32>,
+    title: Option<&str>,
+) -> Option<EpisodeInfo> {
+    Some(EpisodeInfo {
+        show: show.into(),
+        year,
+        season,
+        episodes,
+        title: title.map(Into::into),
+    })
+}
+
+#[test]
+fn test_season_episode() {
+    assert_eq!(
+        parse_episode("Breaking.Bad.S01E02.720p.mkv"),
+        info("breaking bad", None, Some(1), vec![2], None)
+    );
+    assert_eq!(
+        parse_episode("breaking bad s1e2"),
+        info("breaking bad", None, Some(1), vec![2], None)
+    );
+}
+
+#[test]
+fn test_cross() {
+    assert_eq!(
+        parse_episode("Breaking Bad - 1x02 - Cat's in the Bag"),
+        info("breaking bad", None, Some(1), vec![2], Some("cat's in the bag"))
+    );
+}
+
+#[test]
+fn test_words() {
+    assert_eq!(
+        parse_episode("The Wire Season 1 Episode 2"),
+        info("the wire", None, Some(1), vec![2], None)
+    );
+    assert_eq!(
+        parse_episode("The Wire - Season 3 Episode 11 - Middle Ground"),
+        info("the wire", None, Some(3), vec![11], Some("middle ground"))
+    );
+}
+
+#[test]
+fn test_multi_episode() {
+    assert_eq!(
+        parse_episode("Friends.S01E01E02.mkv"),
+        info("friends", None, Some(1), vec![1, 2], None)
+    );
+    assert_eq!(
+        parse_episode("Friends.S01E01-E03.mkv"),
+        info("friends", None, Some(1), vec![1, 2, 3], None)
+    );
+    assert_eq!(
+        parse_episode("Friends - S01E01-02 - The Pilot"),
+        info("friends", None, Some(1), vec![1, 2], Some("the pilot"))
+    );
+    // Numbers starting the title are not episodes.
+    assert_eq!(
+        parse_episode("Show.S01E02.24.Hours"),
+        info("show", None, Some(1), vec![2], Some("24 hours"))
+    );
+    assert_eq!(
+        parse_episode("Show.S01E05.100.Days"),
+        info("show", None, Some(1), vec![5], Some("100 days"))
+    );
+    // Long numbers and wide ranges are not episode ranges.
+    let episodes = |name| parse_episode(name).map(|info| info.episodes);
+    assert_eq!(episodes("Show.S01E01-E99999999.mkv"), Some(vec![1]));
+    assert_eq!(episodes("Show.S01E01-E2147483647.mkv"), Some(vec![1]));
+    assert_eq!(episodes("Show.S01E01-S01E2147483647.mkv"), Some(vec![1]));
+    assert_eq!(episodes("Show.S01E01-E200.mkv"), Some(vec![1]));
+}
+
+#[test]
+fn test_show_year() {
+    assert_eq!(
+        parse_episode("Doctor.Who.2005.S03E10.mkv"),
+        info("doctor who", Some(2005), Some(3), vec![10], None)
+    );
+    assert_eq!(
+        parse_episode("Doctor Who (2005) - S03E10 - Blink [1080p]"),
+        info("doctor who", Some(2005), Some(3), vec![10], Some("blink"))
+    );
+}
+
+#[test]
+fn test_episode_title() {
+    assert_eq!(
+        parse_episode("Breaking.Bad.S05E14.Ozymandias.720p.x264"),
+        info("breaking bad", None, Some(5), vec![14], Some("ozymandias"))
+    );
+}
+
+#[test]
+fn test_absolute() {
+    assert_eq!(
+        parse_episode("[HorribleSubs] One Punch Man - 12 [1080p]"),
+        info("one punch man", None, None, vec![12], None)
+    );
+    assert_eq!(
+        parse_episode("[Group] One Piece - 1000v2 [720p]"),
+        info("one piece", None, None, vec![1000], None)
+    );
+    assert_eq!(
+        parse_episode("[Group] Cowboy Bebop - ep05 - Ballad of Fallen Angels"),
+        info(
+            "cowboy bebop",
+            None,
+            None,
+            vec![5],
+            Some("ballad of fallen angels")
+        )
+    );
+}
+
+#[test]
+fn test_not_an_episode() {
+    assert_eq!(parse_episode("American.Psycho.2000.1080p"), None);
+    assert_eq!(parse_episode("1920x1080"), None);
+    assert_eq!(parse_episode("Apollo 13 (1995)"), None);
+    assert_eq!(parse_episode("[psycho] Snatch! 2005"), None);
+}
+
+#[test]
+fn test_season_folder() {
+    assert_eq!(parse_season_folder("Season 2"), Some(2));
+    assert_eq!(parse_season_folder("Saison 03"), Some(3));
+    assert_eq!(parse_season_folder("The.Wire.S03.720p"), Some(3));
+    assert_eq!(parse_season_folder("Specials"), Some(0));
+    assert_eq!(parse_season_folder("The.Matrix.1999"), None);
+    assert_eq!(parse_season_folder("Subs"), None);
+}
+
+#[test]
+fn test_episode_in_season() {
+    assert_eq!(
+        parse_episode_in_season("02", 2),
+        info("", None, Some(2), vec![2], None)
+    );
+    assert_eq!(
+        parse_episode_in_season("01 - Serenity", 1),
+        info("", None, Some(1), vec![1], Some("serenity"))
+    );
+    assert_eq!(
+        parse_episode_in_season("Firefly - E03 - Bushwhacked [720p]", 1),
+        info("firefly", None, Some(1), vec![3], Some("bushwhacked"))
+    );
+    assert_eq!(
+        parse_episode_in_season("Episode 4", 1),
+        info("", None, Some(1), vec![4], None)
+    );
+    assert_eq!(
+        parse_episode_in_season("Firefly.S01E05.mkv", 2),
+        info("firefly", None, Some(1), vec![5], None)
+    );
+    assert_eq!(parse_episode_in_season("Extras", 1), None);
+}
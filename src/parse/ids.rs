@@ -0,0 +1,154 @@
+use std::fmt;
+
+use super::parse_filename;
+
+/// Id of a movie or show in an external database, found in a filename or an NFO file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternalId {
+    /// IMDb id, such as `tt0133093`.
+    Imdb(String),
+    Tmdb(i64),
+}
+
+impl fmt::Display for ExternalId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExternalId::Imdb(ref id) => write!(f, "{}", id),
+            ExternalId::Tmdb(id) => write!(f, "tmdb-{}", id),
+        }
+    }
+}
+
+/// IMDb ids are `tt` followed by 7 or 8 digits.
+pub fn is_imdb_id(text: &str) -> bool {
+    text.len() >= 9 && text.len() <= 10 && text.starts_with("tt")
+        && text[2..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_tag(text: &str) -> bool {
+    match text {
+        "tmdb" | "tmdbid" | "imdb" | "imdbid" => true,
+        _ => false,
+    }
+}
+
+/// Remove the braces around tags such as `{tmdb-603}`, which are not separators.
+fn trim(token: &str) -> String {
+    token
+        .trim_matches(|c| c == '{' || c == '}')
+        .to_lowercase()
+}
+
+/// Token that is part of an id, such as `tt0133093` or the `tmdb` of `{tmdb-603}`. These tokens are
+/// not part of the title.
+pub fn is_id_token(token: &str) -> bool {
+    let token = trim(token);
+    let tag = token.split('=').next().unwrap_or("");
+    is_imdb_id(&token) || is_tag(tag)
+}
+
+fn parse_tag(tag: &str, value: &str) -> Option<ExternalId> {
+    match tag {
+        "tmdb" | "tmdbid" => value.parse().ok().map(ExternalId::Tmdb),
+        "imdb" | "imdbid" if is_imdb_id(value) => Some(ExternalId::Imdb(value.into())),
+        _ => None,
+    }
+}
+
+/// Find an id in a filename, either an IMDb id or a tag such as `{tmdb-603}`, `[tmdbid=603]` or
+/// `{imdb-tt0133093}`.
+pub fn find_in_filename(filename: &str) -> Option<ExternalId> {
+    let tokens: Vec<String> = parse_filename(filename)
+        .iter()
+        .map(|t| trim(t))
+        .collect();
+    for (idx, token) in tokens.iter().enumerate() {
+        if is_imdb_id(token) {
+            return Some(ExternalId::Imdb(token.clone()));
+        }
+        // The value is in the same token with `=`, or in the next token with `-`.
+        let mut parts = token.splitn(2, '=');
+        let tag = parts.next().unwrap_or("");
+        let value = parts.next().or_else(|| tokens.get(idx + 1).map(String::as_str));
+        if let Some(id) = value.and_then(|value| parse_tag(tag, value)) {
+            return Some(id);
+        }
+    }
+    None
+}
+
+/// Digits at the start of the text.
+fn leading_digits(text: &str) -> &str {
+    let end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    &text[..end]
+}
+
+/// Find an id in the text of an NFO file, either an IMDb id or a link to a TMDB page.
+pub fn find_in_text(text: &str) -> Option<ExternalId> {
+    for marker in &["themoviedb.org/movie/", "themoviedb.org/tv/"] {
+        if let Some(pos) = text.find(marker) {
+            if let Ok(id) = leading_digits(&text[pos + marker.len()..]).parse() {
+                return Some(ExternalId::Tmdb(id));
+            }
+        }
+    }
+
+    let mut rest = text;
+    while let Some(pos) = rest.find("tt") {
+        let before = rest[..pos].chars().next_back();
+        let digits = leading_digits(&rest[pos + 2..]);
+        let id = &rest[pos..pos + 2 + digits.len()];
+        if before.map_or(true, |c| !c.is_alphanumeric()) && is_imdb_id(id) {
+            return Some(ExternalId::Imdb(id.into()));
+        }
+        rest = &rest[pos + 2..];
+    }
+    None
+}
+
+#[test]
+fn test_find_in_filename() {
+    assert_eq!(
+        find_in_filename("The.Matrix.1999.tt0133093.1080p"),
+        Some(ExternalId::Imdb("tt0133093".into()))
+    );
+    assert_eq!(
+        find_in_filename("The Matrix (1999) {tmdb-603}"),
+        Some(ExternalId::Tmdb(603))
+    );
+    assert_eq!(
+        find_in_filename("Firefly (2002) [tmdbid=1437]"),
+        Some(ExternalId::Tmdb(1437))
+    );
+    assert_eq!(
+        find_in_filename("The Matrix {imdb-tt0133093}"),
+        Some(ExternalId::Imdb("tt0133093".into()))
+    );
+    assert_eq!(find_in_filename("The.Matrix.1999.1080p"), None);
+    assert_eq!(find_in_filename("Scott Pilgrim vs the World"), None);
+}
+
+#[test]
+fn test_find_in_text() {
+    assert_eq!(
+        find_in_text("http://www.imdb.com/title/tt0133093/\n"),
+        Some(ExternalId::Imdb("tt0133093".into()))
+    );
+    assert_eq!(
+        find_in_text("<movie><uniqueid type=\"imdb\">tt0133093</uniqueid></movie>"),
+        Some(ExternalId::Imdb("tt0133093".into()))
+    );
+    assert_eq!(
+        find_in_text("https://www.themoviedb.org/movie/603-the-matrix"),
+        Some(ExternalId::Tmdb(603))
+    );
+    assert_eq!(find_in_text("Scott Pilgrim vs the World"), None);
+}
+
+#[test]
+fn test_is_id_token() {
+    assert!(is_id_token("tt0133093"));
+    assert!(is_id_token("{tmdb"));
+    assert!(is_id_token("tmdbid=603"));
+    assert!(!is_id_token("matrix"));
+}
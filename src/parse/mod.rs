@@ -1,7 +1,10 @@
 use std::ops::Deref;
 
+pub mod episode;
+pub mod ids;
 pub mod metadata;
 pub mod movie;
+pub mod release;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Scope {
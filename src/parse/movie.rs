@@ -1,7 +1,8 @@
 use std::cmp;
 
-use super::metadata;
-use super::{is_year, parse_filename, Scope};
+use super::ids;
+use super::release::{self, ReleaseInfo};
+use super::{is_year, parse_filename, Scope, Token};
 
 /// Try to extract title and year from filename.
 ///
@@ -18,7 +19,27 @@ use super::{is_year, parse_filename, Scope};
 pub fn parse_movie(filename: &str) -> (String, Option<i32>) {
     let filename = filename.to_lowercase();
     let tokens = parse_filename(&filename);
+    let (title_start, title_end, year_idx) = split(&tokens);
 
+    (
+        tokens[title_start..title_end]
+            .iter()
+            .map(|t| t.text)
+            .collect::<Vec<_>>()
+            .join(" "),
+        year_idx.map(|idx| tokens[idx].parse().unwrap()),
+    )
+}
+
+/// Parse the release information that follows the title in the filename, see release::parse.
+pub fn parse_release(filename: &str) -> ReleaseInfo {
+    let tokens = parse_filename(filename);
+    let (_, title_end, _) = split(&tokens);
+    release::parse(filename, &tokens[title_end..])
+}
+
+/// Positions of the first token of the title, of the token after it and of the year.
+fn split(tokens: &[Token]) -> (usize, usize, Option<usize>) {
     let mut first_word_index = None;
     let mut year_candidates = vec![];
     let mut first_metadata_token = None;
@@ -31,7 +52,7 @@ pub fn parse_movie(filename: &str) -> (String, Option<i32>) {
             year_candidates.push(idx);
         }
         if first_metadata_token.is_none() {
-            if metadata::ALL.contains(&token.text) {
+            if release::is_metadata(&tokens[idx..]) || ids::is_id_token(token) {
                 first_metadata_token = Some(idx);
             }
         }
@@ -40,26 +61,22 @@ pub fn parse_movie(filename: &str) -> (String, Option<i32>) {
     let first_word_index = first_word_index.unwrap_or(0); // Error maybe?
     let first_metadata_token = first_metadata_token.unwrap_or(tokens.len());
 
-    let mut title_tokens = &tokens[first_word_index..first_metadata_token];
+    let mut title_end = first_metadata_token;
     let mut year = None;
 
     if let Some(&year_idx) = year_candidates.last() {
         let min_idx = cmp::min(year_idx, first_metadata_token);
-        let new_title_tokens = &tokens[first_word_index..min_idx];
-        if !new_title_tokens.is_empty() {
-            title_tokens = new_title_tokens;
-            year = Some(tokens[year_idx].parse().unwrap());
+        if first_word_index < min_idx {
+            title_end = min_idx;
+            year = Some(year_idx);
         }
     }
 
-    (
-        title_tokens
-            .iter()
-            .map(|t| t.text)
-            .collect::<Vec<_>>()
-            .join(" "),
-        year,
-    )
+    if first_word_index < title_end {
+        title_end = first_word_index
+            + release::title_without_edition(&tokens[first_word_index..title_end]);
+    }
+    (first_word_index, title_end, year)
 }
 
 #[test]
@@ -92,6 +109,11 @@ fn test_metadata() {
         parse_movie("Truman Show 1080p.mkv"),
         ("truman show".into(), None)
     );
+    // Words of titles that are also release terms.
+    assert_eq!(
+        parse_movie("Mr.Hollands.Opus.1995.1080p"),
+        ("mr hollands opus".into(), Some(1995))
+    );
 }
 
 #[test]
@@ -109,3 +131,44 @@ fn test_year_within_scope() {
         ("night of the living dead".into(), Some(1968))
     )
 }
+
+#[test]
+fn test_ids_not_in_title() {
+    assert_eq!(
+        parse_movie("The Matrix {tmdb-603}"),
+        ("the matrix".into(), None)
+    );
+    assert_eq!(
+        parse_movie("The.Matrix.tt0133093.1999"),
+        ("the matrix".into(), Some(1999))
+    );
+}
+
+#[test]
+fn test_release() {
+    assert_eq!(
+        parse_movie("The.Matrix.1999.1080p.WEB-DL.DD5.1.H.264-GROUP"),
+        ("the matrix".into(), Some(1999))
+    );
+    let release = parse_release("The.Matrix.1999.1080p.WEB-DL.DD5.1.H.264-GROUP");
+    assert_eq!(release.resolution, Some("1080p".into()));
+    assert_eq!(release.source, Some(release::Source::WebDl));
+    assert_eq!(release.audio, Some("ac3".into()));
+    assert_eq!(release.channels, Some("5.1".into()));
+    assert_eq!(release.codec, Some("h264".into()));
+    assert_eq!(release.group, Some("GROUP".into()));
+    // Words of the title are not release information.
+    let release = parse_release("Charlotte's.Web.1973.DVDRip");
+    assert_eq!(release.source, Some(release::Source::Dvd));
+    assert_eq!(parse_release("The.Final.Cut.2004").edition, None);
+}
+
+#[test]
+fn test_edition() {
+    let filename = "Blade.Runner.Directors.Cut.1982.1080p";
+    assert_eq!(parse_movie(filename), ("blade runner".into(), Some(1982)));
+    assert_eq!(parse_release(filename).edition, Some("Director's Cut".into()));
+    let filename = "Blade Runner (1982) {edition-The Final Cut}";
+    assert_eq!(parse_movie(filename), ("blade runner".into(), Some(1982)));
+    assert_eq!(parse_release(filename).edition, Some("The Final Cut".into()));
+}
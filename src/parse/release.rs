@@ -0,0 +1,461 @@
+use std::fmt;
+
+use database::Hdr;
+use super::{Scope, Token};
+
+/// Source of a release, from the worst to the best.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Source {
+    Cam,
+    Telesync,
+    Telecine,
+    Screener,
+    Dvd,
+    Hdtv,
+    WebRip,
+    WebDl,
+    BluRay,
+    /// The untouched streams of a Blu-ray.
+    Remux,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Source::Cam => "CAM",
+            Source::Telesync => "TS",
+            Source::Telecine => "TC",
+            Source::Screener => "SCR",
+            Source::Dvd => "DVD",
+            Source::Hdtv => "HDTV",
+            Source::WebRip => "WEBRip",
+            Source::WebDl => "WEB-DL",
+            Source::BluRay => "BluRay",
+            Source::Remux => "Remux",
+        })
+    }
+}
+
+/// Information about a release found in its name, such as
+/// `The.Matrix.1999.1080p.BluRay.DTS-HD.MA.5.1.x264-GROUP`.
+///
+/// Codecs are named by their families, `x264` and `AVC` are both `h264`, see
+/// container::codec_family.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReleaseInfo {
+    pub resolution: Option<String>,
+    pub source: Option<Source>,
+    pub codec: Option<String>,
+    pub audio: Option<String>,
+    pub channels: Option<String>, // Such as `5.1`.
+    pub hdr: Option<Hdr>,
+    pub edition: Option<String>,
+    pub group: Option<String>,
+    pub proper: bool,
+    pub repack: bool,
+    pub three_d: bool,
+}
+
+impl ReleaseInfo {
+    /// Whether the name tells anything about the quality of the release. Files named by the
+    /// templates of the libraries usually do not.
+    pub fn has_quality(&self) -> bool {
+        self.resolution.is_some() || self.source.is_some() || self.hdr.is_some() || self.proper
+            || self.repack
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Term {
+    Resolution(&'static str),
+    Source(Source),
+    Codec(&'static str),
+    Audio(&'static str),
+    Hdr(Hdr),
+    Edition(&'static str),
+    Proper,
+    Repack,
+    ThreeD,
+    /// Metadata that is not recorded, such as `10bit`.
+    Other,
+}
+
+impl Term {
+    /// Whether the term ends the title. Editions and flags such as `proper` can also be words of
+    /// titles, they are only looked for after the title.
+    fn is_strong(&self) -> bool {
+        match *self {
+            Term::Edition(_) | Term::Proper | Term::Repack | Term::ThreeD => false,
+            _ => true,
+        }
+    }
+}
+
+/// Terms that are also common words, such as `Charlotte's Web` or `Mr. Holland's Opus`. They do
+/// not end titles on their own.
+static COMMON_WORDS: &'static [&'static str] = &[
+    "cam", "dvd", "web", "dd", "dv", "avc", "opus", "atmos", "screener", "telecine"
+];
+
+/// Words of the release terms, lowercase, as split by parse_filename.
+static TERMS: &'static [(&'static [&'static str], Term)] = &[
+    (&["2160p"], Term::Resolution("2160p")),
+    (&["4k"], Term::Resolution("2160p")),
+    (&["uhd"], Term::Resolution("2160p")),
+    (&["1080p"], Term::Resolution("1080p")),
+    (&["1080i"], Term::Resolution("1080p")),
+    (&["720p"], Term::Resolution("720p")),
+    (&["576p"], Term::Resolution("576p")),
+    (&["576i"], Term::Resolution("576p")),
+    (&["480p"], Term::Resolution("480p")),
+    (&["480i"], Term::Resolution("480p")),
+    (&["360p"], Term::Resolution("360p")),
+    (&["240p"], Term::Resolution("240p")),
+    (&["cam"], Term::Source(Source::Cam)),
+    (&["camrip"], Term::Source(Source::Cam)),
+    (&["hdcam"], Term::Source(Source::Cam)),
+    (&["telesync"], Term::Source(Source::Telesync)),
+    (&["hdts"], Term::Source(Source::Telesync)),
+    (&["telecine"], Term::Source(Source::Telecine)),
+    (&["screener"], Term::Source(Source::Screener)),
+    (&["dvdscr"], Term::Source(Source::Screener)),
+    (&["bdscr"], Term::Source(Source::Screener)),
+    (&["dvdrip"], Term::Source(Source::Dvd)),
+    (&["dvd", "rip"], Term::Source(Source::Dvd)),
+    (&["dvd"], Term::Source(Source::Dvd)),
+    (&["dvdr"], Term::Source(Source::Dvd)),
+    (&["dvd5"], Term::Source(Source::Dvd)),
+    (&["dvd9"], Term::Source(Source::Dvd)),
+    (&["hdtv"], Term::Source(Source::Hdtv)),
+    (&["pdtv"], Term::Source(Source::Hdtv)),
+    (&["sdtv"], Term::Source(Source::Hdtv)),
+    (&["tvrip"], Term::Source(Source::Hdtv)),
+    (&["webrip"], Term::Source(Source::WebRip)),
+    (&["web", "rip"], Term::Source(Source::WebRip)),
+    (&["webdl"], Term::Source(Source::WebDl)),
+    (&["web", "dl"], Term::Source(Source::WebDl)),
+    (&["web"], Term::Source(Source::WebDl)),
+    (&["bluray"], Term::Source(Source::BluRay)),
+    (&["blu", "ray"], Term::Source(Source::BluRay)),
+    (&["bdrip"], Term::Source(Source::BluRay)),
+    (&["brrip"], Term::Source(Source::BluRay)),
+    (&["remux"], Term::Source(Source::Remux)),
+    (&["bdremux"], Term::Source(Source::Remux)),
+    (&["x264"], Term::Codec("h264")),
+    (&["h264"], Term::Codec("h264")),
+    (&["h", "264"], Term::Codec("h264")),
+    (&["avc"], Term::Codec("h264")),
+    (&["x265"], Term::Codec("h265")),
+    (&["h265"], Term::Codec("h265")),
+    (&["h", "265"], Term::Codec("h265")),
+    (&["hevc"], Term::Codec("h265")),
+    (&["xvid"], Term::Codec("mpeg4")),
+    (&["divx"], Term::Codec("mpeg4")),
+    (&["av1"], Term::Codec("av1")),
+    (&["vp9"], Term::Codec("vp9")),
+    (&["mpeg2"], Term::Codec("mpeg2")),
+    (&["aac"], Term::Audio("aac")),
+    (&["ac3"], Term::Audio("ac3")),
+    (&["dd"], Term::Audio("ac3")),
+    (&["eac3"], Term::Audio("eac3")),
+    (&["e", "ac3"], Term::Audio("eac3")),
+    (&["ddp"], Term::Audio("eac3")),
+    (&["dd+"], Term::Audio("eac3")),
+    (&["dts"], Term::Audio("dts")),
+    (&["dtshd"], Term::Audio("dts")),
+    (&["dts", "hd"], Term::Audio("dts")),
+    (&["dts", "hd", "ma"], Term::Audio("dts")),
+    (&["dts", "x"], Term::Audio("dts")),
+    (&["truehd"], Term::Audio("truehd")),
+    (&["flac"], Term::Audio("flac")),
+    (&["opus"], Term::Audio("opus")),
+    (&["mp3"], Term::Audio("mp3")),
+    (&["lpcm"], Term::Audio("pcm")),
+    (&["hdr"], Term::Hdr(Hdr::Hdr10)),
+    (&["hdr10"], Term::Hdr(Hdr::Hdr10)),
+    (&["hdr10+"], Term::Hdr(Hdr::Hdr10)),
+    (&["hdr10plus"], Term::Hdr(Hdr::Hdr10)),
+    (&["hlg"], Term::Hdr(Hdr::Hlg)),
+    (&["dv"], Term::Hdr(Hdr::DolbyVision)),
+    (&["dovi"], Term::Hdr(Hdr::DolbyVision)),
+    (&["dolby", "vision"], Term::Hdr(Hdr::DolbyVision)),
+    (&["director's", "cut"], Term::Edition("Director's Cut")),
+    (&["directors", "cut"], Term::Edition("Director's Cut")),
+    (&["extended"], Term::Edition("Extended")),
+    (&["extended", "cut"], Term::Edition("Extended")),
+    (&["extended", "edition"], Term::Edition("Extended")),
+    (&["theatrical"], Term::Edition("Theatrical")),
+    (&["theatrical", "cut"], Term::Edition("Theatrical")),
+    (&["unrated"], Term::Edition("Unrated")),
+    (&["uncut"], Term::Edition("Uncut")),
+    (&["remastered"], Term::Edition("Remastered")),
+    (&["imax"], Term::Edition("IMAX")),
+    (&["final", "cut"], Term::Edition("Final Cut")),
+    (&["special", "edition"], Term::Edition("Special Edition")),
+    (&["ultimate", "edition"], Term::Edition("Ultimate Edition")),
+    (&["ultimate", "cut"], Term::Edition("Ultimate Edition")),
+    (&["criterion"], Term::Edition("Criterion")),
+    (&["proper"], Term::Proper),
+    (&["repack"], Term::Repack),
+    (&["rerip"], Term::Repack),
+    (&["3d"], Term::ThreeD),
+    (&["sbs"], Term::ThreeD),
+    (&["hsbs"], Term::ThreeD),
+    (&["half", "sbs"], Term::ThreeD),
+    (&["hou"], Term::ThreeD),
+    (&["half", "ou"], Term::ThreeD),
+    (&["10bit"], Term::Other),
+    (&["8bit"], Term::Other),
+    (&["atmos"], Term::Other),
+    (&["hdrip"], Term::Other),
+];
+
+/// The longest term at the start of the tokens, with its number of tokens.
+fn term(tokens: &[Token]) -> Option<(Term, usize)> {
+    TERMS
+        .iter()
+        .filter(|&&(words, _)| {
+            words.len() <= tokens.len()
+                && words
+                    .iter()
+                    .zip(tokens)
+                    .all(|(word, token)| token.text.eq_ignore_ascii_case(word))
+        })
+        .max_by_key(|&&(words, _)| words.len())
+        .map(|&(words, term)| (term, words.len()))
+}
+
+/// Start of the edition tags of Plex, such as `{edition-Director's Cut}`, once split by
+/// parse_filename.
+fn is_edition_tag(token: &Token) -> bool {
+    token.text.eq_ignore_ascii_case("{edition")
+}
+
+/// Edition of a Plex edition tag in the filename. Known editions get their usual names, so that
+/// `{edition-directors cut}` is the `Director's Cut` found in other filenames.
+fn edition_tag(filename: &str) -> Option<String> {
+    let tag = "{edition-";
+    let start = (0..filename.len())
+        .find(|&i| filename.get(i..i + tag.len()).map_or(false, |t| t.eq_ignore_ascii_case(tag)))?
+        + tag.len();
+    let end = start + filename[start..].find('}')?;
+    let edition = filename[start..end].trim();
+    if edition.is_empty() {
+        return None;
+    }
+    let tokens = super::parse_filename(edition);
+    match term(&tokens) {
+        Some((Term::Edition(name), len)) if len == tokens.len() => Some(name.into()),
+        _ => Some(edition.into()),
+    }
+}
+
+/// Whether the tokens start with metadata that ends the title of a movie or an episode.
+pub fn is_metadata(tokens: &[Token]) -> bool {
+    if tokens.first().map_or(false, is_edition_tag) {
+        return true;
+    }
+    match term(tokens) {
+        Some((_, 1)) if COMMON_WORDS.iter().any(|w| tokens[0].text.eq_ignore_ascii_case(w)) => {
+            false
+        }
+        Some((term, _)) => term.is_strong(),
+        None => false,
+    }
+}
+
+/// Length of the title without the edition at its end, such as `Blade Runner (Final Cut)`. The
+/// edition must be between parentheses or brackets, or have several words and leave at least two
+/// words of title, `The Final Cut` is a title.
+pub fn title_without_edition(title: &[Token]) -> usize {
+    for start in 1..title.len() {
+        if let Some((Term::Edition(_), len)) = term(&title[start..]) {
+            let scoped = title[start..].iter().all(|t| t.scope != Scope::Normal);
+            if start + len == title.len() && (scoped || (len > 1 && start >= 2)) {
+                return start;
+            }
+        }
+    }
+    title.len()
+}
+
+/// Channel layouts split by parse_filename, `5.1` or `DD5.1`, with the audio format before them.
+fn channels(tokens: &[Token]) -> Option<(Option<Term>, String)> {
+    let (first, second) = match (tokens.get(0), tokens.get(1)) {
+        (Some(first), Some(second)) if second.text == "0" || second.text == "1" => {
+            (first.text, second.text)
+        }
+        _ => return None,
+    };
+    let main = first.chars().last()?;
+    if !main.is_digit(10) || main == '0' {
+        return None;
+    }
+    let prefix = &first[..first.len() - 1];
+    let audio = if prefix.is_empty() {
+        None
+    } else {
+        match term(&[Token::new(prefix, tokens[0].scope)]) {
+            Some((term @ Term::Audio(_), _)) => Some(term),
+            _ => return None,
+        }
+    };
+    Some((audio, format!("{}.{}", main, second)))
+}
+
+/// Parse the release information of the tokens that follow the title of `filename`. The release
+/// group is the end of the filename, after a dash, such as `GROUP` in `...x264-GROUP`.
+///
+/// Plex edition tags are used over the editions found in the tokens.
+pub fn parse(filename: &str, tokens: &[Token]) -> ReleaseInfo {
+    let mut info = ReleaseInfo::default();
+    let mut found = false;
+    let mut idx = 0;
+    // Whether the last token is part of a term.
+    let mut last_used = false;
+    while idx < tokens.len() {
+        if is_edition_tag(&tokens[idx]) {
+            let len = tokens[idx..]
+                .iter()
+                .position(|t| t.text.ends_with('}'))
+                .map_or(tokens.len() - idx, |end| end + 1);
+            info.edition = edition_tag(filename);
+            found = true;
+            idx += len;
+            last_used = idx == tokens.len();
+            continue;
+        }
+        let (term, len) = match channels(&tokens[idx..]) {
+            Some((audio, channels)) => {
+                info.channels = Some(channels);
+                (audio.unwrap_or(Term::Other), 2)
+            }
+            None => match term(&tokens[idx..]) {
+                Some(found) => found,
+                None => {
+                    idx += 1;
+                    continue;
+                }
+            },
+        };
+        match term {
+            Term::Resolution(resolution) => info.resolution = Some(resolution.into()),
+            Term::Source(source) => {
+                // `BluRay.Remux` is a remux.
+                info.source = Some(info.source.map_or(source, |s| s.max(source)));
+            }
+            Term::Codec(codec) => info.codec = Some(codec.into()),
+            Term::Audio(audio) => {
+                if info.audio.is_none() {
+                    info.audio = Some(audio.into());
+                }
+            }
+            // Dolby Vision releases also have an HDR10 layer.
+            Term::Hdr(hdr) => {
+                if info.hdr != Some(Hdr::DolbyVision) {
+                    info.hdr = Some(hdr);
+                }
+            }
+            Term::Edition(edition) => {
+                if info.edition.is_none() && edition_tag(filename).is_none() {
+                    info.edition = Some(edition.into());
+                }
+            }
+            Term::Proper => info.proper = true,
+            Term::Repack => info.repack = true,
+            Term::ThreeD => info.three_d = true,
+            Term::Other => {}
+        }
+        found = true;
+        idx += len;
+        last_used = idx == tokens.len();
+    }
+
+    if let (Some(last), Some(pos)) = (tokens.last(), filename.rfind('-')) {
+        let group = &filename[pos + 1..];
+        if found && !last_used && group == last.text {
+            info.group = Some(group.into());
+        }
+    }
+    info
+}
+
+#[cfg(test)]
+fn parse_all(filename: &str) -> ReleaseInfo {
+    parse(filename, &super::parse_filename(filename))
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(
+        parse_all("1080p.BluRay.REMUX.DTS-HD.MA.5.1.AVC-FraMeSToR"),
+        ReleaseInfo {
+            resolution: Some("1080p".into()),
+            source: Some(Source::Remux),
+            codec: Some("h264".into()),
+            audio: Some("dts".into()),
+            channels: Some("5.1".into()),
+            group: Some("FraMeSToR".into()),
+            ..ReleaseInfo::default()
+        }
+    );
+    assert_eq!(
+        parse_all("2160p.WEB-DL.DDP5.1.Atmos.DV.HDR.H.265-GROUP"),
+        ReleaseInfo {
+            resolution: Some("2160p".into()),
+            source: Some(Source::WebDl),
+            codec: Some("h265".into()),
+            audio: Some("eac3".into()),
+            channels: Some("5.1".into()),
+            hdr: Some(Hdr::DolbyVision),
+            group: Some("GROUP".into()),
+            ..ReleaseInfo::default()
+        }
+    );
+    assert_eq!(
+        parse_all("Directors.Cut.PROPER.3D.HSBS.720p.HDTV.XviD"),
+        ReleaseInfo {
+            resolution: Some("720p".into()),
+            source: Some(Source::Hdtv),
+            codec: Some("mpeg4".into()),
+            edition: Some("Director's Cut".into()),
+            proper: true,
+            three_d: true,
+            ..ReleaseInfo::default()
+        }
+    );
+    assert_eq!(
+        parse_all("{edition-Director's Cut}.Extended.1080p").edition,
+        Some("Director's Cut".into())
+    );
+    assert_eq!(
+        parse_all("{edition-directors cut}").edition,
+        Some("Director's Cut".into())
+    );
+    assert_eq!(
+        parse_all("{edition-Signed Copy}").edition,
+        Some("Signed Copy".into())
+    );
+    // Without metadata, the end of the filename is not a group.
+    assert_eq!(parse_all("Spider-Man"), ReleaseInfo::default());
+    assert_eq!(parse_all("720p.WEB-DL").group, None);
+}
+
+#[test]
+fn test_is_metadata() {
+    assert!(is_metadata(&super::parse_filename("WEB-DL.x264")));
+    assert!(is_metadata(&super::parse_filename("x264")));
+    assert!(!is_metadata(&super::parse_filename("Web")));
+    assert!(!is_metadata(&super::parse_filename("Extended")));
+    assert!(is_metadata(&super::parse_filename("{edition-Extended}")));
+}
+
+#[test]
+fn test_title_without_edition() {
+    let length = |title| title_without_edition(&super::parse_filename(title));
+    assert_eq!(length("Blade Runner (Final Cut)"), 2);
+    assert_eq!(length("Blade Runner Directors Cut"), 2);
+    assert_eq!(length("The Final Cut"), 3);
+    assert_eq!(length("Alien Extended"), 2);
+}
@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use database::ImageKind;
+
+use serde_json;
+
+use error;
+use matching;
+use provider::{Media, MetadataProvider};
+use tmdb::images::Images;
+use tmdb::{find, movie, search, tv};
+
+/// In-memory metadata provider, used to import files offline and in tests.
+///
+/// A fixture can be loaded from a JSON file:
+///
+/// ```json
+/// {
+///     "movies": [{ "movie": { "id": 603, "title": "The Matrix", ... } }],
+///     "shows": [{ "show": { "id": 1437, "name": "Firefly", ... }, "seasons": [...] }]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Fixture {
+    pub movies: Vec<MovieFixture>,
+    pub shows: Vec<ShowFixture>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MovieFixture {
+    pub movie: search::Movie,
+    #[serde(default)]
+    pub details: Option<movie::Details>,
+    #[serde(default)]
+    pub images: Images,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShowFixture {
+    pub show: search::Show,
+    #[serde(default)]
+    pub imdb_id: Option<String>,
+    #[serde(default)]
+    pub seasons: Vec<tv::Season>,
+    #[serde(default)]
+    pub images: Images,
+}
+
+/// Every word of the query is in one of the titles.
+fn matches(query: &str, titles: &[&str]) -> bool {
+    let query = matching::normalize(query);
+    titles.iter().any(|title| {
+        let title = matching::normalize(title);
+        let words: Vec<&str> = title.split(' ').collect();
+        query.split(' ').all(|word| words.contains(&word))
+    })
+}
+
+fn not_found(what: &str, id: i64) -> error::Error {
+    error::Error::NotFound(format!("{} {}", what, id))
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        Fixture::default()
+    }
+
+    pub fn open<A>(path: A) -> Result<Fixture, error::Error>
+    where
+        A: AsRef<Path>,
+    {
+        let file = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    fn find_movie(&self, id: i64) -> Result<&MovieFixture, error::Error> {
+        self.movies
+            .iter()
+            .find(|m| m.movie.id == id)
+            .ok_or_else(|| not_found("movie", id))
+    }
+
+    fn find_show(&self, id: i64) -> Result<&ShowFixture, error::Error> {
+        self.shows
+            .iter()
+            .find(|s| s.show.id == id)
+            .ok_or_else(|| not_found("show", id))
+    }
+}
+
+impl MetadataProvider for Fixture {
+    fn search_movie(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Movie>, error::Error> {
+        Ok(self.movies
+            .iter()
+            .map(|m| &m.movie)
+            .filter(|m| matches(query, &[&m.title, &m.original_title]))
+            .filter(|m| year.map_or(true, |y| m.release_date.starts_with(&y.to_string())))
+            .cloned()
+            .collect())
+    }
+
+    fn movie(&self, id: i64) -> Result<search::Movie, error::Error> {
+        self.find_movie(id).map(|m| m.movie.clone())
+    }
+
+    fn movie_details(&self, id: i64) -> Result<movie::Details, error::Error> {
+        self.find_movie(id)?
+            .details
+            .clone()
+            .ok_or_else(|| not_found("details of movie", id))
+    }
+
+    /// Movies are found by the IMDb id of their details, shows by their own IMDb id.
+    fn find_imdb(&self, imdb_id: &str) -> Result<find::Results, error::Error> {
+        let id = Some(imdb_id);
+        Ok(find::Results {
+            movie_results: self.movies
+                .iter()
+                .filter(|m| {
+                    let imdb_id = m.details.as_ref().and_then(|d| d.imdb_id.as_ref());
+                    imdb_id.map(String::as_str) == id
+                })
+                .map(|m| m.movie.clone())
+                .collect(),
+            tv_results: self.shows
+                .iter()
+                .filter(|s| s.imdb_id.as_ref().map(String::as_str) == id)
+                .map(|s| s.show.clone())
+                .collect(),
+            tv_episode_results: vec![],
+        })
+    }
+
+    fn search_show(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Show>, error::Error> {
+        Ok(self.shows
+            .iter()
+            .map(|s| &s.show)
+            .filter(|s| matches(query, &[&s.name, &s.original_name]))
+            .filter(|s| year.map_or(true, |y| s.year() == Some(y)))
+            .cloned()
+            .collect())
+    }
+
+    fn show(&self, id: i64) -> Result<search::Show, error::Error> {
+        self.find_show(id).map(|s| s.show.clone())
+    }
+
+    fn show_details(&self, id: i64) -> Result<tv::Details, error::Error> {
+        let show = self.find_show(id)?;
+        Ok(tv::Details {
+            id,
+            name: show.show.name.clone(),
+            seasons: show.seasons
+                .iter()
+                .map(|season| tv::SeasonSummary {
+                    season_number: season.season_number,
+                    episode_count: season.episodes.len() as i32,
+                })
+                .collect(),
+        })
+    }
+
+    fn season(&self, show_id: i64, season: i32) -> Result<tv::Season, error::Error> {
+        self.find_show(show_id)?
+            .seasons
+            .iter()
+            .find(|s| s.season_number == season)
+            .cloned()
+            .ok_or_else(|| not_found(&format!("season {} of show", season), show_id))
+    }
+
+    fn images(&self, media: Media, id: i64) -> Result<Images, error::Error> {
+        match media {
+            Media::Movie => self.find_movie(id).map(|m| m.images.clone()),
+            Media::Show => self.find_show(id).map(|s| s.images.clone()),
+        }
+    }
+
+    /// Fixtures do not contain images.
+    fn download_image(
+        &self,
+        _kind: ImageKind,
+        file_path: &str,
+        _size: &str,
+        _dest: &Path,
+    ) -> Result<(), error::Error> {
+        Err(error::Error::NotFound(format!("image {}", file_path)))
+    }
+}
+
+#[test]
+fn test_fixture_search() {
+    let fixture = Fixture::open("testdata/fixtures/tmdb.json").unwrap();
+    let results = fixture.search_movie("the matrix", Some(1999)).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, 603);
+    assert!(fixture.search_movie("the matrix", Some(2003)).unwrap().is_empty());
+    assert!(fixture.search_movie("matrix reloaded", None).unwrap().is_empty());
+
+    let details = fixture.show_details(1437).unwrap();
+    assert_eq!(details.resolve_absolute(2), Some((1, 2)));
+    assert!(fixture.season(1437, 2).is_err());
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use database::ImageKind;
+use error;
+use tmdb::images::Images;
+use tmdb::{find, movie, search, tv};
+
+pub mod fixture;
+
+pub use self::fixture::Fixture;
+
+/// Kind of media that images are requested for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Media {
+    Movie,
+    Show,
+}
+
+/// Source of the metadata of movies and shows.
+///
+/// The results use the TMDB types, other providers convert their results to them. Ids are the
+/// provider's own ids.
+pub trait MetadataProvider {
+    /// Make sure the provider can be used, for instance that its credentials are accepted.
+    fn verify(&self) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn search_movie(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Movie>, error::Error>;
+    fn movie(&self, id: i64) -> Result<search::Movie, error::Error>;
+    /// Details of the movie, with its credits and release dates.
+    fn movie_details(&self, id: i64) -> Result<movie::Details, error::Error>;
+    /// Movies, shows and episodes with the IMDb id.
+    fn find_imdb(&self, imdb_id: &str) -> Result<find::Results, error::Error>;
+
+    fn search_show(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Show>, error::Error>;
+    fn show(&self, id: i64) -> Result<search::Show, error::Error>;
+    /// Seasons of the show, used to resolve absolute episode numbers.
+    fn show_details(&self, id: i64) -> Result<tv::Details, error::Error>;
+    fn season(&self, show_id: i64, season: i32) -> Result<tv::Season, error::Error>;
+
+    fn images(&self, media: Media, id: i64) -> Result<Images, error::Error>;
+    /// Download the image at the given size, such as `w780`, or at the closest size available.
+    fn download_image(
+        &self,
+        kind: ImageKind,
+        file_path: &str,
+        size: &str,
+        dest: &Path,
+    ) -> Result<(), error::Error>;
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::path::Path;
+
+use container;
+use database::{Movie, Track, TrackKind};
+use error;
+use parse::release::{ReleaseInfo, Source};
+
+/// Quality of a copy of a movie or an episode. Copies are compared by resolution first, then by
+/// source, HDR, fixed releases and finally by bitrate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Quality {
+    /// Height of the resolution class, such as 1080, or 0 if unknown.
+    pub resolution: u32,
+    pub source: Option<Source>,
+    pub hdr: bool,
+    /// Proper and repack releases fix a broken release of the same quality.
+    pub fixed: bool,
+    /// In bits per second, or 0 if unknown.
+    pub bitrate: u64,
+}
+
+impl Quality {
+    /// Quality from the information of the file, or from the release name for what the headers
+    /// of the file do not tell.
+    pub fn new(release: &ReleaseInfo, tracks: &[Track], bitrate: Option<u64>) -> Quality {
+        let video = tracks.iter().find(|t| t.kind == TrackKind::Video);
+        let resolution = video
+            .and_then(|v| Some(container::resolution(v.width?, v.height?)))
+            .or_else(|| release.resolution.as_ref().map(String::as_str));
+        Quality {
+            resolution: resolution
+                .and_then(|r| r.trim_right_matches('p').parse().ok())
+                .unwrap_or(0),
+            source: release.source,
+            // HDR can be signalled in the video stream only, trust the name unless the
+            // container says the video is SDR.
+            hdr: match video {
+                Some(video) if video.hdr.is_some() || video.sdr => video.hdr.is_some(),
+                _ => release.hdr.is_some(),
+            },
+            fixed: release.proper || release.repack,
+            bitrate: bitrate.unwrap_or(0),
+        }
+    }
+
+    pub fn of_movie(movie: &Movie) -> Quality {
+        Quality::new(&movie.release, &movie.tracks, movie.bitrate)
+    }
+
+    /// Quality of a file from its release and its headers.
+    pub fn of_file(path: &Path, release: &ReleaseInfo) -> Quality {
+        match container::read(path) {
+            Ok(info) => Quality::new(release, &info.tracks, info.bitrate),
+            Err(error::Error::Unsupported(_)) => Quality::new(release, &[], None),
+            Err(err) => {
+                println!("Unable to read the headers of {}: {}", path.display(), err);
+                Quality::new(release, &[], None)
+            }
+        }
+    }
+
+    /// The quality without what only the name of the release tells, to compare with a copy whose
+    /// release is unknown.
+    pub fn without_release(&self) -> Quality {
+        Quality {
+            source: None,
+            fixed: false,
+            ..*self
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if self.resolution > 0 {
+            parts.push(format!("{}p", self.resolution));
+        }
+        if let Some(source) = self.source {
+            parts.push(source.to_string());
+        }
+        if self.hdr {
+            parts.push("HDR".into());
+        }
+        if self.fixed {
+            parts.push("PROPER".into());
+        }
+        if self.bitrate > 0 {
+            parts.push(format!("{} kb/s", self.bitrate / 1000));
+        }
+        if parts.is_empty() {
+            f.write_str("unknown quality")
+        } else {
+            f.write_str(&parts.join(" "))
+        }
+    }
+}
+
+#[test]
+fn test_quality() {
+    let quality = |name| Quality::new(&::parse::movie::parse_release(name), &[], None);
+    assert!(quality("Movie.2010.1080p.WEB-DL") > quality("Movie.2010.720p.BluRay"));
+    assert!(quality("Movie.2010.1080p.BluRay") > quality("Movie.2010.1080p.WEBRip"));
+    assert!(quality("Movie.2010.1080p.BluRay.REMUX") > quality("Movie.2010.1080p.BluRay"));
+    assert!(quality("Movie.2010.720p.HDTV.PROPER") > quality("Movie.2010.720p.HDTV"));
+    assert!(quality("Movie.2010.DVDRip") > quality("Movie.2010"));
+    assert_eq!(quality("Movie.2010.2160p.HDR.BluRay").to_string(), "2160p BluRay HDR");
+
+    // The headers of the file are trusted over the name.
+    let mut video = Track {
+        kind: TrackKind::Video,
+        codec: "V_MPEG4/ISO/AVC".into(),
+        lang: None,
+        name: None,
+        default: true,
+        forced: false,
+        width: Some(1280),
+        height: Some(720),
+        hdr: None,
+        sdr: false,
+        channels: None,
+    };
+    let release = ::parse::movie::parse_release("Movie.2010.1080p.BluRay");
+    assert_eq!(Quality::new(&release, &[video.clone()], None).resolution, 720);
+    video.width = None;
+    assert_eq!(Quality::new(&release, &[video], None).resolution, 1080);
+}
@@ -0,0 +1,479 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use error;
+use parse::parse_filename;
+
+/// Format of a subtitle file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// SubRip, `.srt`.
+    Srt,
+    /// WebVTT, `.vtt`.
+    Vtt,
+    /// Advanced SubStation Alpha, `.ass`.
+    Ass,
+    /// SubStation Alpha, `.ssa`.
+    Ssa,
+    /// SAMI, `.smi`.
+    Smi,
+    /// Universal Subtitle Format, `.usf`.
+    Usf,
+    /// MicroDVD, a `.sub` file on its own.
+    MicroDvd,
+    /// DVD bitmaps, an `.idx` file with the images in the `.sub` file of the same name.
+    VobSub,
+    /// Blu-ray bitmaps, `.sup`.
+    Pgs,
+}
+
+impl Format {
+    pub fn from_extension(ext: &str) -> Option<Format> {
+        Some(match ext.to_lowercase().as_str() {
+            "srt" => Format::Srt,
+            "vtt" => Format::Vtt,
+            "ass" => Format::Ass,
+            "ssa" => Format::Ssa,
+            "smi" => Format::Smi,
+            "usf" => Format::Usf,
+            "sub" => Format::MicroDvd,
+            "idx" => Format::VobSub,
+            "sup" => Format::Pgs,
+            _ => return None,
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Option<Format> {
+        Format::from_extension(&path.extension()?.to_string_lossy())
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Format::Srt => "srt",
+            Format::Vtt => "vtt",
+            Format::Ass => "ass",
+            Format::Ssa => "ssa",
+            Format::Smi => "smi",
+            Format::Usf => "usf",
+            Format::MicroDvd => "sub",
+            Format::VobSub => "idx",
+            Format::Pgs => "sup",
+        }
+    }
+
+    /// Text formats, as opposed to bitmaps which cannot be read without OCR.
+    pub fn is_text(&self) -> bool {
+        match *self {
+            Format::VobSub | Format::Pgs => false,
+            _ => true,
+        }
+    }
+
+    /// Whether subtitles of this format can be converted to the other format.
+    pub fn can_convert(&self, to: Format) -> bool {
+        match (*self, to) {
+            (Format::Srt, Format::Vtt) | (Format::Vtt, Format::Srt) => true,
+            _ => false,
+        }
+    }
+}
+
+/// File with the same stem and the given extension, in lowercase or uppercase, if it exists.
+fn sibling(path: &Path, ext: &str) -> Option<PathBuf> {
+    let candidates = [path.with_extension(ext), path.with_extension(ext.to_uppercase())];
+    candidates.iter().find(|p| p.is_file()).cloned()
+}
+
+/// The `.sub` file holding the images of a VobSub `.idx` file.
+pub fn companion(path: &Path) -> Option<PathBuf> {
+    match Format::from_path(path) {
+        Some(Format::VobSub) => sibling(path, "sub"),
+        _ => None,
+    }
+}
+
+/// Whether the file is the `.sub` half of a VobSub pair, which goes along with its `.idx` file
+/// rather than being a subtitle on its own.
+pub fn is_companion(path: &Path) -> bool {
+    Format::from_path(path) == Some(Format::MicroDvd) && sibling(path, "idx").is_some()
+}
+
+/// Languages recognised in subtitle names, by ISO 639-1 code, with their ISO 639-2 codes and
+/// names.
+static LANGUAGES: &'static [(&'static str, &'static [&'static str])] = &[
+    ("en", &["en", "eng", "english"]),
+    ("fr", &["fr", "fre", "fra", "french", "francais", "français"]),
+    ("es", &["es", "spa", "spanish", "espanol", "español", "castellano"]),
+    ("de", &["de", "ger", "deu", "german", "deutsch"]),
+    ("it", &["it", "ita", "italian", "italiano"]),
+    ("pt", &["pt", "por", "pob", "ptbr", "portuguese", "portugues", "português", "brazilian"]),
+    ("nl", &["nl", "dut", "nld", "dutch", "nederlands"]),
+    ("sv", &["sv", "swe", "swedish", "svenska"]),
+    ("da", &["da", "dan", "danish", "dansk"]),
+    ("no", &["no", "nor", "nob", "norwegian", "norsk"]),
+    ("fi", &["fi", "fin", "finnish", "suomi"]),
+    ("pl", &["pl", "pol", "polish", "polski"]),
+    ("cs", &["cs", "cze", "ces", "czech"]),
+    ("hu", &["hu", "hun", "hungarian"]),
+    ("ro", &["ro", "rum", "ron", "romanian"]),
+    ("el", &["el", "gre", "ell", "greek"]),
+    ("tr", &["tr", "tur", "turkish"]),
+    ("ru", &["ru", "rus", "russian"]),
+    ("he", &["he", "heb", "hebrew"]),
+    ("ar", &["ar", "ara", "arabic"]),
+    ("ja", &["ja", "jpn", "japanese"]),
+    ("zh", &["zh", "chi", "zho", "chinese"]),
+    ("ko", &["ko", "kor", "korean"]),
+];
+
+/// Common words of the languages detected in the text of subtitles.
+static STOPWORDS: &'static [(&'static str, &'static [&'static str])] = &[
+    ("en", &["the", "and", "you", "that", "what", "this", "have", "with", "your", "are", "not"]),
+    ("fr", &["le", "la", "les", "et", "est", "vous", "je", "pas", "que", "une", "des", "qui"]),
+    ("es", &["el", "los", "las", "que", "es", "por", "una", "pero", "qué", "para", "está", "yo"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ich", "sie", "du", "ein", "was", "wir"]),
+    ("it", &["il", "che", "non", "è", "per", "una", "sono", "ma", "di", "gli", "cosa", "questo"]),
+    ("pt", &["o", "que", "não", "é", "uma", "você", "para", "com", "os", "eu", "isso", "do"]),
+    ("nl", &["de", "het", "een", "en", "is", "niet", "ik", "je", "dat", "wat", "van", "maar"]),
+];
+
+/// Minimum number of common words needed to detect the language of a text.
+const MIN_STOPWORDS: usize = 10;
+/// Only the start of the subtitles is read to detect their language.
+const DETECT_BYTES: u64 = 64 * 1024;
+
+/// ISO 639-1 code of a language name or code, such as `eng` or `French`.
+pub fn language(token: &str) -> Option<&'static str> {
+    let token = token.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|&&(_, names)| names.contains(&token.as_str()))
+        .map(|&(code, _)| code)
+}
+
+/// Language and flags of a subtitle file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+    /// ISO 639-1 code.
+    pub lang: Option<String>,
+    /// Only the foreign or alien dialogues are subtitled.
+    pub forced: bool,
+    /// Subtitles for the deaf and hard of hearing, which describe the sounds.
+    pub sdh: bool,
+}
+
+impl Tags {
+    /// Tags used in the subtitle's name in the library, such as `en.forced`, empty when there are
+    /// none. This is the format Kodi, Plex and Jellyfin understand.
+    pub fn suffix(&self) -> String {
+        let mut parts = vec![];
+        if let Some(ref lang) = self.lang {
+            parts.push(lang.as_str());
+        }
+        if self.forced {
+            parts.push("forced");
+        }
+        if self.sdh {
+            parts.push("sdh");
+        }
+        parts.join(".")
+    }
+}
+
+/// Find the tags at the end of a name, such as `Movie.2010.en.forced` or `2_English`.
+///
+/// Tokens are read from the end until one is not a tag, so that words of the title, such as the
+/// `No` of `No.Country.for.Old.Men`, are not mistaken for languages.
+pub fn parse_tags(name: &str) -> Tags {
+    let mut tags = Tags::default();
+    for token in parse_filename(name).iter().rev() {
+        let token = token.to_lowercase();
+        match token.as_str() {
+            "forced" => tags.forced = true,
+            "sdh" | "hi" | "cc" => tags.sdh = true,
+            "default" | "full" | "subs" | "subtitles" => {}
+            // Numbers, as in `2_English.srt`, which is how rips name their tracks.
+            _ if token.len() <= 2 && token.chars().all(|c| c.is_ascii_digit()) => {}
+            _ => match language(&token) {
+                Some(lang) if tags.lang.is_none() => tags.lang = Some(lang.into()),
+                _ => break,
+            },
+        }
+    }
+    tags
+}
+
+/// Detect the language of the text by counting the common words of each language.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut counts: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|&(code, stopwords)| {
+            let count = words
+                .iter()
+                .filter(|w| stopwords.contains(&w.as_str()))
+                .count();
+            (code, count)
+        })
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+    let (code, best) = counts[0];
+    let second = counts[1].1;
+    // Some words are shared by several languages, the best one must be well ahead.
+    if best >= MIN_STOPWORDS && best * 2 >= second * 3 {
+        Some(code)
+    } else {
+        None
+    }
+}
+
+/// Tags of the subtitle file of the video, from its name, then the names of its directories
+/// below the directory of the video, such as `Subs/English/`, then the language of its text.
+///
+/// Subtitles named after the video only have their tags after the name of the video, so that
+/// `It.srt` for `It.mkv` is not in Italian.
+pub fn tags(path: &Path, video: &Path) -> Tags {
+    let video_dir = video.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().to_lowercase());
+    let video_stem = video
+        .file_stem()
+        .map_or(String::new(), |s| s.to_string_lossy().to_lowercase());
+    let name = if stem == video_stem {
+        ""
+    } else if stem.starts_with(&format!("{}.", video_stem)) {
+        &stem[video_stem.len() + 1..]
+    } else {
+        &stem
+    };
+    let mut tags = parse_tags(name);
+    if tags.lang.is_some() {
+        return tags;
+    }
+
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if !current.starts_with(video_dir) || current == video_dir {
+            break;
+        }
+        if let Some(name) = current.file_name() {
+            let dir_tags = parse_tags(&name.to_string_lossy());
+            if dir_tags.lang.is_some() {
+                tags.lang = dir_tags.lang;
+                return tags;
+            }
+        }
+        dir = current.parent();
+    }
+
+    if Format::from_path(path).map_or(false, |format| format.is_text()) {
+        let mut bytes = vec![];
+        if let Ok(file) = File::open(path) {
+            if file.take(DETECT_BYTES).read_to_end(&mut bytes).is_ok() {
+                let text = String::from_utf8_lossy(&bytes);
+                tags.lang = detect_language(&text).map(String::from);
+            }
+        }
+    }
+    tags
+}
+
+/// Split the text in blocks separated by empty lines, without the byte order mark and the
+/// carriage returns.
+fn blocks(text: &str) -> Vec<Vec<&str>> {
+    let text = text.trim_left_matches('\u{feff}');
+    let mut blocks = vec![];
+    let mut block = vec![];
+    for line in text.lines() {
+        let line = line.trim_right_matches('\r');
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(block);
+                block = vec![];
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Convert a timestamp from `00:01:02,500` to `00:01:02.500`, or the reverse. WebVTT allows
+/// leaving out the hours, SubRip does not.
+fn timestamp(time: &str, from: char, to: char) -> String {
+    let time = time.replace(from, &to.to_string());
+    if time.matches(':').count() == 1 {
+        format!("00:{}", time)
+    } else {
+        time
+    }
+}
+
+/// Convert the timing line of a cue, dropping the WebVTT cue settings.
+fn timing(line: &str, from: char, to: char) -> Option<String> {
+    let mut parts = line.split("-->");
+    let start = parts.next()?.trim();
+    let end = parts.next()?.split_whitespace().next()?;
+    Some(format!(
+        "{} --> {}",
+        timestamp(start, from, to),
+        timestamp(end, from, to)
+    ))
+}
+
+/// Convert SubRip subtitles to WebVTT.
+pub fn srt_to_vtt(text: &str) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for block in blocks(text) {
+        let timing_line = match block.iter().position(|line| line.contains("-->")) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let timing = match timing(block[timing_line], ',', '.') {
+            Some(timing) => timing,
+            None => continue,
+        };
+        vtt.push('\n');
+        vtt.push_str(&timing);
+        vtt.push('\n');
+        for line in &block[timing_line + 1..] {
+            vtt.push_str(line);
+            vtt.push('\n');
+        }
+    }
+    vtt
+}
+
+/// Convert WebVTT subtitles to SubRip. Comments, styles and regions are dropped.
+pub fn vtt_to_srt(text: &str) -> String {
+    let mut srt = String::new();
+    let mut number = 0;
+    for block in blocks(text) {
+        // The header, comments, styles and regions have no timing line.
+        let timing_line = match block.iter().position(|line| line.contains("-->")) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let timing = match timing(block[timing_line], '.', ',') {
+            Some(timing) => timing,
+            None => continue,
+        };
+        number += 1;
+        if number > 1 {
+            srt.push('\n');
+        }
+        srt.push_str(&format!("{}\n{}\n", number, timing));
+        for line in &block[timing_line + 1..] {
+            srt.push_str(line);
+            srt.push('\n');
+        }
+    }
+    srt
+}
+
+/// Convert the subtitle file to the format, writing the result to the destination.
+pub fn convert(src: &Path, dst: &Path, to: Format) -> Result<(), error::Error> {
+    let mut bytes = vec![];
+    File::open(src)?.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let converted = match (Format::from_path(src), to) {
+        (Some(Format::Srt), Format::Vtt) => srt_to_vtt(&text),
+        (Some(Format::Vtt), Format::Srt) => vtt_to_srt(&text),
+        _ => {
+            let what = format!("converting {} to {}", src.display(), to.extension());
+            return Err(error::Error::Unsupported(what));
+        }
+    };
+    if let Some(dir) = dst.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    File::create(dst)?.write_all(converted.as_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_tags() {
+    assert_eq!(parse_tags("The.Matrix.1999.en").lang, Some("en".into()));
+    assert_eq!(
+        parse_tags("The.Matrix.1999.fr.forced"),
+        Tags {
+            lang: Some("fr".into()),
+            forced: true,
+            sdh: false,
+        }
+    );
+    assert_eq!(
+        parse_tags("2_English_SDH"),
+        Tags {
+            lang: Some("en".into()),
+            forced: false,
+            sdh: true,
+        }
+    );
+    assert_eq!(parse_tags("No.Country.for.Old.Men.2007"), Tags::default());
+    assert_eq!(parse_tags("The.Matrix.1999"), Tags::default());
+}
+
+#[test]
+fn test_detect_language() {
+    let english = "1\n00:00:01,000 --> 00:00:03,000\nWhat is that? You have to see this.\n\n\
+                   2\n00:00:04,000 --> 00:00:06,000\nThe man and the woman are not with your \
+                   friends, that is what you have.";
+    assert_eq!(detect_language(english), Some("en"));
+    let french = "Je ne sais pas ce que vous voulez. C'est la vie et les gens qui sont des amis, \
+                  le chat est une bête qui ne dort pas.";
+    assert_eq!(detect_language(french), Some("fr"));
+    assert_eq!(detect_language("Hello there."), None);
+}
+
+#[test]
+fn test_tags() {
+    let dir = Path::new("testdata/The.Matrix.1999");
+    let video = dir.join("The.Matrix.1999.mkv");
+    assert_eq!(tags(&dir.join("Subs/English/1.srt"), &video).lang, Some("en".into()));
+    assert_eq!(tags(&dir.join("Subs/eng.srt"), &video).lang, Some("en".into()));
+
+    // Only the tags after the name of the video are read.
+    let dir = Path::new("movies");
+    let it = tags(&dir.join("It.srt"), &dir.join("It.mkv"));
+    assert_eq!(it.lang, None);
+    let german = tags(&dir.join("The.Good.German.srt"), &dir.join("The.Good.German.mkv"));
+    assert_eq!(german.lang, None);
+    let forced = tags(&dir.join("It.de.forced.srt"), &dir.join("It.mkv"));
+    assert_eq!(forced.lang, Some("de".into()));
+    assert!(forced.forced);
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(Format::from_extension("IDX"), Some(Format::VobSub));
+    assert_eq!(Format::from_path(Path::new("movie.en.ass")), Some(Format::Ass));
+    assert_eq!(Format::from_extension("mkv"), None);
+    assert!(!Format::Pgs.is_text());
+    assert!(Format::Srt.can_convert(Format::Vtt));
+    assert!(!Format::Ass.can_convert(Format::Srt));
+}
+
+#[test]
+fn test_convert() {
+    let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:03,500\r\nWake up, Neo.\r\n\r\n\
+               2\r\n00:01:04,000 --> 00:01:06,000\r\n<i>Follow the white rabbit.</i>\r\n";
+    let vtt = "WEBVTT\n\n00:00:01.000 --> 00:00:03.500\nWake up, Neo.\n\n\
+               00:01:04.000 --> 00:01:06.000\n<i>Follow the white rabbit.</i>\n";
+    assert_eq!(srt_to_vtt(srt), vtt);
+
+    let vtt = "WEBVTT - The Matrix\n\nNOTE translated by hand\n\n\
+               intro\n00:01.000 --> 00:03.500 align:start\nWake up, Neo.\n\n\
+               01:04.000 --> 01:06.000\n<i>Follow the white rabbit.</i>\n";
+    let srt = "1\n00:00:01,000 --> 00:00:03,500\nWake up, Neo.\n\n\
+               2\n00:01:04,000 --> 00:01:06,000\n<i>Follow the white rabbit.</i>\n";
+    assert_eq!(vtt_to_srt(vtt), srt);
+}
@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+
+use tmdb::cache::{self, Cache};
+
+/// Show the number of responses cached and their size, by kind of endpoint.
+pub fn info(cache: &Cache) {
+    let entries = cache.entries().expect("unable to read cache");
+    println!("Cache directory: {}", cache.dir().display());
+    if entries.is_empty() {
+        println!("The cache is empty.");
+        return;
+    }
+
+    // Count, expired count and size of every kind of entry.
+    let mut kinds: BTreeMap<&str, (usize, usize, u64)> = BTreeMap::new();
+    for &(ref entry, size) in &entries {
+        let stats = kinds.entry(cache::kind(&entry.key)).or_insert((0, 0, 0));
+        stats.0 += 1;
+        if entry.is_expired() {
+            stats.1 += 1;
+        }
+        stats.2 += size;
+    }
+    for (kind, &(count, expired, size)) in &kinds {
+        println!(
+            "    {:<14} {:>6} response(s), {:>6} expired, {:>8} KiB",
+            kind,
+            count,
+            expired,
+            size / 1024
+        );
+    }
+}
+
+/// Remove the cached responses, only the expired ones if `expired_only` is true.
+pub fn purge(cache: &Cache, expired_only: bool) {
+    let count = cache.purge(expired_only).expect("unable to purge cache");
+    println!("Removed {} cached response(s).", count);
+}
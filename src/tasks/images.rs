@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use config::Config;
+use database::{Database, Image, ImageKind};
+use fs;
+use provider::MetadataProvider;
+
+fn name(kind: ImageKind) -> &'static str {
+    match kind {
+        ImageKind::Poster => "poster",
+        ImageKind::Backdrop => "backdrop",
+    }
+}
+
+/// Path of the image of the video, with the names used by Kodi and Plex. Videos sharing their
+/// directory have their images prefixed by their name, as in `Movie-poster.jpg`.
+fn image_path(kind: ImageKind, video: &Path) -> Option<PathBuf> {
+    let name = match kind {
+        ImageKind::Poster => "poster.jpg",
+        ImageKind::Backdrop => "fanart.jpg",
+    };
+    let dir = video.parent()?;
+    if fs::only_video(video) {
+        Some(dir.join(name))
+    } else {
+        let stem = video.file_stem()?.to_string_lossy();
+        Some(dir.join(format!("{}-{}", stem, name)))
+    }
+}
+
+/// Download the poster and the backdrop in the directory of the video, returning the images that
+/// are present. Existing files are kept unless `force` is true. Failed downloads are skipped.
+pub fn download(
+    provider: &MetadataProvider,
+    config: &Config,
+    video: &Path,
+    poster: Option<&str>,
+    backdrop: Option<&str>,
+    force: bool,
+) -> Vec<Image> {
+    let wanted = [
+        (ImageKind::Poster, poster, &config.images.poster_size),
+        (ImageKind::Backdrop, backdrop, &config.images.backdrop_size),
+    ];
+
+    let mut images = vec![];
+    for &(kind, file_path, size) in &wanted {
+        let (file_path, path) = match (file_path, image_path(kind, video)) {
+            (Some(file_path), Some(path)) => (file_path, path),
+            _ => continue,
+        };
+        if force || !path.exists() {
+            if let Err(err) = provider.download_image(kind, file_path, size, &path) {
+                println!(
+                    "Unable to download the {} of {}: {}",
+                    name(kind),
+                    video.display(),
+                    err
+                );
+                continue;
+            }
+        }
+        images.push(Image { kind, path });
+    }
+    images
+}
+
+/// Download the images of the movies of the library that are missing some, or of every movie if
+/// `force` is true.
+pub fn backfill(db: &mut Database, provider: &MetadataProvider, config: &Config, force: bool) {
+    let mut count = 0;
+    for movie in db.movies_mut() {
+        let complete = movie.images.len() == 2 && movie.images.iter().all(|i| i.path.exists());
+        if (complete && !force) || !movie.path.exists() {
+            continue;
+        }
+        let api_movie = match provider.movie(movie.tmdb_id) {
+            Ok(api_movie) => api_movie,
+            Err(err) => {
+                println!("Unable to get {}: {}", movie.title, err);
+                continue;
+            }
+        };
+        movie.images = download(
+            provider,
+            config,
+            &movie.path,
+            api_movie.poster_path.as_ref().map(String::as_str),
+            api_movie.backdrop_path.as_ref().map(String::as_str),
+            force,
+        );
+        count += 1;
+    }
+    println!("Updated the images of {} movie(s).", count);
+}
+
+#[test]
+fn test_image_path() {
+    use std::fs::{create_dir_all, remove_dir_all, File};
+
+    let dir = ::std::env::temp_dir().join("merovingian-test-images");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let matrix = dir.join("The Matrix (1999).mkv");
+    let heat = dir.join("Heat (1995).mkv");
+
+    File::create(&matrix).unwrap();
+    assert_eq!(image_path(ImageKind::Poster, &matrix), Some(dir.join("poster.jpg")));
+    // The second movie is not there yet.
+    assert_eq!(
+        image_path(ImageKind::Poster, &heat),
+        Some(dir.join("Heat (1995)-poster.jpg"))
+    );
+    File::create(&heat).unwrap();
+    assert_eq!(
+        image_path(ImageKind::Backdrop, &matrix),
+        Some(dir.join("The Matrix (1999)-fanart.jpg"))
+    );
+    remove_dir_all(&dir).unwrap();
+}
@@ -1,125 +1,843 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use database::{Database, Movie, Subtitle};
+use classify::{self, Kind};
+use config::{Config, Duplicates, Library, Title};
+use container::{self, MediaInfo};
+use database::{self, Database, Movie, Subtitle};
+use error;
 use fingerprint;
 use fs::{self, Entry};
+use matching::{self, Outcome, Review};
+use nfo;
 use parse;
-use tmdb::search;
+use parse::episode::EpisodeInfo;
+use parse::ids::{self, ExternalId};
+use tasks::images;
+use template::Template;
+use provider::MetadataProvider;
+use quality::Quality;
+use subtitle;
+use tmdb::{self, search};
 use tree::{Node, Tree};
 
-fn build_movie_path(
-    base: &Path,
-    ext: &str,
-    movie: &search::Movie,
-    duplicate_index: i32,
-) -> PathBuf {
-    let version = if duplicate_index > 1 {
+/// Number of actors stored for every movie.
+const CAST: usize = 10;
+
+fn format_version(duplicate_index: i32) -> String {
+    if duplicate_index > 1 {
         format!(".v{}", duplicate_index)
     } else {
         format!("")
+    }
+}
+
+/// Add the release information found in the filename to the template values.
+fn insert_file_values(values: &mut HashMap<&'static str, String>, stem: &str) {
+    let release = parse::movie::parse_release(stem);
+    let fields = [
+        ("edition", release.edition.clone()),
+        ("edition_tag", release.edition.map(|e| format!("{{edition-{}}}", e))),
+        ("quality", release.resolution),
+        ("source", release.source.map(|s| s.to_string())),
+        ("codec", release.codec),
+        ("audio", release.audio),
+        ("hdr", release.hdr.map(|h| h.to_string())),
+        ("group", release.group),
+    ];
+    for &(field, ref value) in &fields {
+        if let Some(ref value) = *value {
+            values.insert(field, value.clone());
+        }
+    }
+}
+
+/// Pick the localized or the original title.
+fn choose_title<'a>(choice: Title, localized: &'a str, original: &'a str) -> &'a str {
+    match choice {
+        Title::Original if !original.is_empty() => original,
+        _ => localized,
+    }
+}
+
+/// Values of the movie template fields, except for the extension. The `title` field is the title
+/// chosen in the configuration.
+fn movie_values(
+    movie: &search::Movie,
+    title: Title,
+    stem: &str,
+    duplicate_index: i32,
+) -> HashMap<&'static str, String> {
+    let mut values = hashmap!{
+        "title" => choose_title(title, &movie.title, &movie.original_title).into(),
+        "original_title" => movie.original_title.clone(),
+        "year" => movie.year().map(|y| y.to_string()).unwrap_or_default(),
+        "tmdb_id" => movie.id.to_string(),
+        "n" => duplicate_index.to_string(),
+        "version" => format_version(duplicate_index),
     };
+    insert_file_values(&mut values, stem);
+    values
+}
+
+/// Copy the details of the movie to its record.
+fn insert_details(movie: &mut Movie, details: &tmdb::movie::Details, country: &str) {
+    movie.tagline = details.tagline.clone();
+    // TMDB uses 0 and empty strings for unknown values.
+    movie.runtime = details.runtime.and_then(|r| if r > 0 { Some(r) } else { None });
+    movie.imdb_id = details
+        .imdb_id
+        .clone()
+        .and_then(|id| if id.is_empty() { None } else { Some(id) });
+    movie.certification = details.certification(country).map(String::from);
+    movie.genres = details.genres.iter().map(|g| g.name.clone()).collect();
+    movie.collection = details.belongs_to_collection.as_ref().map(|c| c.name.clone());
+    movie.languages = details
+        .spoken_languages
+        .iter()
+        .map(|l| l.iso_639_1.clone())
+        .collect();
+    movie.directors = details.directors().into_iter().map(String::from).collect();
+    movie.cast = details.cast(CAST).into_iter().map(String::from).collect();
+}
+
+fn season_dirname(season: i32) -> String {
+    if season == 0 {
+        "Specials".into()
+    } else {
+        format!("Season {:02}", season)
+    }
+}
+
+/// Format the episode numbers, `S01E02` or `S01E02-E03` for multi-episode files.
+fn format_episode_numbers(season: i32, episodes: &[i32]) -> String {
+    format!("S{:02}E{}", season, format_episodes(episodes))
+}
+
+/// Format the episode numbers without the season, `02` or `02-E03` for multi-episode files.
+fn format_episodes(episodes: &[i32]) -> String {
+    let mut text = format!("{:02}", episodes[0]);
+    if episodes.len() > 1 {
+        text.push_str(&format!("-E{:02}", episodes[episodes.len() - 1]));
+    }
+    text
+}
 
-    let dirname = fs::filter_filename(&format!("{} ({})", movie.title, movie.year()));
-    let filename = fs::filter_filename(&format!(
-        "{} ({}){}.{}",
-        movie.title,
-        movie.year(),
-        version,
-        ext
-    ));
-
-    let mut path = base.to_owned();
-    path.push(dirname);
-    path.push(filename);
-    path
-}
-
-fn process_video_file(
-    tree: &Tree<Entry>,
-    node: Node,
-    file: &Entry,
+/// Values of the episode template fields, except for the extension. The `show` field is the title
+/// chosen in the configuration.
+fn episode_values(
+    show: &search::Show,
+    show_title: Title,
+    season: i32,
+    episodes: &[i32],
+    title: Option<&str>,
     stem: &str,
+    duplicate_index: i32,
+) -> HashMap<&'static str, String> {
+    let mut values = hashmap!{
+        "show" => choose_title(show_title, &show.name, &show.original_name).into(),
+        "original_show" => show.original_name.clone(),
+        "show_year" => show.year().map(|y| y.to_string()).unwrap_or_default(),
+        "show_tmdb_id" => show.id.to_string(),
+        "season" => format!("{:02}", season),
+        "season_dir" => season_dirname(season),
+        "episode" => format_episodes(episodes),
+        "episode_title" => title.unwrap_or("").into(),
+        "n" => duplicate_index.to_string(),
+        "version" => format_version(duplicate_index),
+    };
+    insert_file_values(&mut values, stem);
+    values
+}
+
+/// Render the template with the given extension.
+fn build_path(
+    template: &Template,
+    base: &Path,
+    values: &mut HashMap<&'static str, String>,
     ext: &str,
-    db: &mut Database,
-) {
+) -> PathBuf {
+    values.insert("ext", ext.into());
+    template.render(base, values)
+}
+
+/// Subtitles of the video in the library, named after the video followed by their tags, such as
+/// `Title (Year).en.forced.srt`. Subtitles that would end up with the same name are numbered.
+///
+/// Subtitles that can be converted to the `convert` format get its extension.
+fn build_subtitles(
+    template: &Template,
+    base: &Path,
+    values: &mut HashMap<&'static str, String>,
+    video: &Path,
+    entries: &[&Entry],
+    convert: Option<subtitle::Format>,
+) -> Vec<Subtitle> {
+    let mut subtitles: Vec<Subtitle> = vec![];
+    for entry in entries {
+        let tags = subtitle::tags(entry.path(), video);
+        let mut parts = vec![];
+        if tags.lang.is_some() || tags.forced || tags.sdh {
+            parts.push(tags.suffix());
+        }
+        let ext = entry.extension().expect("subtitle has no extension");
+        match (subtitle::Format::from_extension(ext), convert) {
+            (Some(from), Some(to)) if from.can_convert(to) => parts.push(to.extension().into()),
+            _ => parts.push(ext.into()),
+        }
+        let mut path = build_path(template, base, values, &parts.join("."));
+        let mut n = 1;
+        while subtitles.iter().any(|s| s.path == path) {
+            n += 1;
+            let mut numbered = parts.clone();
+            numbered.insert(parts.len() - 1, n.to_string());
+            path = build_path(template, base, values, &numbered.join("."));
+        }
+        subtitles.push(Subtitle {
+            lang: tags.lang,
+            forced: tags.forced,
+            sdh: tags.sdh,
+            path,
+        });
+    }
+    subtitles
+}
+
+fn process_movie_file(ctx: &mut Context, node: Node, stem: &str, ext: &str) {
+    let tree = ctx.tree;
+    let file = tree.data(node);
     let hash = fingerprint::file(file).expect("failed to hash");
-    if let Some(movie) = db.match_fingerprint(&hash) {
+    if let Some(movie) = ctx.db.match_fingerprint(&hash) {
         println!(
             "{} is already in the library at {}",
             file.display(),
             movie.path.display()
         );
     } else {
-        let (movie, year) = parse::movie::parse_movie(&stem);
-        let mut paged = search::movie(&movie, year).expect("api fail");
-
-        let api_movie = paged.results.remove(0);
-
-        let duplicate_index = db.duplicates(api_movie.id)
-            .last()
-            .map(|m| m.duplicate_index)
-            .unwrap_or(0) + 1;
+        let (mut movie, mut year) = parse::movie::parse_movie(&stem);
+        let info = read_nfo(tree, node);
+        // A NFO file was written by someone who knew what the movie is, trust it over the filename.
+        if let Some(title) = info.title {
+            movie = title;
+            year = info.year.or(year);
+        }
+        // The NFO file describes this very video, its id is more reliable than the names.
+        let id = info.id.or_else(|| filename_id(tree, node, 2));
+        let options = &ctx.options.matching;
+        let result = matching::identify(ctx.provider, file, id.as_ref(), &movie, year, options);
+        let api_movie: search::Movie = match result {
+            Ok(Outcome::Matched(api_movie)) => api_movie,
+            Ok(Outcome::Skipped) => return,
+            Ok(Outcome::Review(review)) => {
+                println!("No confident match for {}, leaving it.", file.display());
+                ctx.report.reviews.push(review);
+                return;
+            }
+            Err(err) => return fail(ctx, file.path(), err),
+        };
+        // Unreleased movies have no release date yet, they can not be named.
+        let year = match api_movie.year() {
+            Some(year) => year,
+            None => {
+                println!("{} has no release date yet, leaving it.", api_movie.title);
+                return;
+            }
+        };
 
-        let sub_entries: Vec<&Entry> = scan_subtitles(&tree, node)
+        let release = parse::movie::parse_release(stem);
+        let media = read_media(file.path());
+        let quality = match media {
+            Some(ref info) => Quality::new(&release, &info.tracks, info.bitrate),
+            None => Quality::new(&release, &[], None),
+        };
+        // Editions are not duplicates of each other, they are only numbered like duplicates when
+        // the template does not tell them apart.
+        let edition = release.edition.clone();
+        let edition = edition.as_ref().map(String::as_str);
+        let duplicates = ctx.db.duplicates(api_movie.id, edition);
+        let copies: Vec<Quality> = duplicates.iter().map(|m| Quality::of_movie(m)).collect();
+        let known = release.has_quality() && duplicates.iter().all(|m| m.release.has_quality());
+        let decision = decide(ctx.config.movies.duplicates, &copies, quality, known);
+        let mut replaced_files = vec![];
+        let duplicate_index = match decision {
+            Decision::Skip => {
+                println!(
+                    "{} is not better than the copy of {} in the library, skipping it.",
+                    file.display(),
+                    api_movie.title
+                );
+                return;
+            }
+            Decision::Replace => {
+                let replaced = ctx.db.duplicates(api_movie.id, edition);
+                for movie in &replaced {
+                    replaced_files.push(movie.path.clone());
+                    replaced_files.extend(subtitle_files(&movie.subtitles));
+                }
+                replaced.iter().map(|m| m.duplicate_index).min().unwrap_or(1)
+            }
+            Decision::Keep => {
+                let template = &ctx.movie_template;
+                let numbered = if template.uses("edition") || template.uses("edition_tag") {
+                    ctx.db.duplicates(api_movie.id, edition)
+                } else {
+                    ctx.db.copies(api_movie.id)
+                };
+                numbered.iter().map(|m| m.duplicate_index).max().unwrap_or(0) + 1
+            }
+        };
+        let sub_entries: Vec<&Entry> = scan_subtitles(tree, node)
             .iter()
             .map(|&n| tree.data(n))
             .collect();
 
-        let path = build_movie_path(db.movies_path(), ext, &api_movie, duplicate_index);
-        let subtitles = sub_entries
-            .iter()
-            .map(|&sub| Subtitle {
-                lang: None,
-                path: build_movie_path(
-                    db.movies_path(),
-                    sub.extension().expect("subtitle has no extension"),
-                    &api_movie,
-                    duplicate_index,
-                ),
-            })
-            .collect();
+        let base = ctx.config.movies_path().to_owned();
+        let mut values = movie_values(&api_movie, ctx.config.title, stem, duplicate_index);
+        let path = build_path(&ctx.movie_template, &base, &mut values, ext);
+        let convert = ctx.config.subtitles.convert;
+        let subtitles = build_subtitles(
+            &ctx.movie_template,
+            &base,
+            &mut values,
+            file.path(),
+            &sub_entries,
+            convert,
+        );
 
-        let movie = Movie {
+        let mut movie = Movie {
             tmdb_id: api_movie.id,
             duplicate_index: duplicate_index,
             title: api_movie.title,
             original_title: api_movie.original_title,
-            year: api_movie.release_date[..4].parse().unwrap(),
+            year: year,
+            edition: release.edition.clone(),
             overview: api_movie.overview,
             path: path.clone(),
             images: vec![],
             subtitles: subtitles,
             fingerprint: hash,
+            release: release,
+            ..Movie::default()
         };
+        // The details are a nice to have, the movie is still imported without them.
+        match ctx.provider.movie_details(movie.tmdb_id) {
+            Ok(details) => insert_details(&mut movie, &details, ctx.config.country()),
+            Err(err) => println!("Unable to get the details of {}: {}", movie.title, err),
+        }
+        if let Some(info) = media {
+            container::update_movie(&mut movie, info);
+            for mismatch in &movie.mismatches {
+                println!("Warning: {}: {}", file.display(), mismatch);
+            }
+        }
 
-        let movie = db.add_movie(movie);
+        let operation = Operation {
+            source: file.path().to_owned(),
+            destination: path.clone(),
+            title: format!("{} ({})", movie.title, movie.year),
+            tmdb_id: movie.tmdb_id,
+            duplicate_index: duplicate_index,
+            subtitles: transfers(&sub_entries, &movie.subtitles),
+            replaced: trash_transfers(&ctx.config.movies, replaced_files),
+        };
+        if !apply(ctx, operation) {
+            return;
+        }
+        if decision == Decision::Replace {
+            let tmdb_id = movie.tmdb_id;
+            ctx.db.retain_movies(|m| m.tmdb_id != tmdb_id || !m.is_edition(edition));
+        }
 
-        fs::best_copy(&file, path).expect("failed to copy movie");
-        for (&entry, sub) in sub_entries.iter().zip(movie.subtitles.iter()) {
-            fs::best_copy(&entry, &sub.path);
+        if ctx.config.images.download && !ctx.options.dry_run {
+            movie.images = images::download(
+                ctx.provider,
+                ctx.config,
+                &path,
+                api_movie.poster_path.as_ref().map(String::as_str),
+                api_movie.backdrop_path.as_ref().map(String::as_str),
+                false,
+            );
+        }
+        if ctx.config.nfo.write && !ctx.options.dry_run {
+            if let Err(err) = nfo::write_movie(&movie) {
+                println!("Unable to write the NFO file of {}: {}", movie.title, err);
+            }
         }
+        ctx.db.add_movie(movie);
+    }
+}
 
+fn process_episode_file(ctx: &mut Context, node: Node, info: &EpisodeInfo, ext: &str) {
+    let tree = ctx.tree;
+    let file = tree.data(node);
+    let hash = fingerprint::file(file).expect("failed to hash");
+    if let Some(episode) = ctx.db.match_episode_fingerprint(&hash) {
         println!(
-            "Added {} to database at {}",
+            "{} is already in the library at {}",
             file.display(),
-            movie.path.display()
+            episode.path.display()
+        );
+        return;
+    }
+
+    let id = filename_id(tree, node, 3).or_else(|| read_nfo(tree, node).id);
+    let options = &ctx.options.matching;
+    let result =
+        matching::identify(ctx.provider, file, id.as_ref(), &info.show, info.year, options);
+    let api_show: search::Show = match result {
+        Ok(Outcome::Matched(api_show)) => api_show,
+        Ok(Outcome::Skipped) => return,
+        Ok(Outcome::Review(review)) => {
+            println!("No confident match for {}, leaving it.", file.display());
+            ctx.report.reviews.push(review);
+            return;
+        }
+        Err(err) => return fail(ctx, file.path(), err),
+    };
+
+    let (season, episodes) = match info.season {
+        Some(season) => (season, info.episodes.clone()),
+        None => {
+            let details = match ctx.provider.show_details(api_show.id) {
+                Ok(details) => details,
+                Err(err) => return fail(ctx, file.path(), err),
+            };
+            match details.resolve_absolute(info.episodes[0]) {
+                Some((season, episode)) => (season, vec![episode]),
+                None => {
+                    println!(
+                        "Episode {} of {} does not exist, skipping {}.",
+                        info.episodes[0],
+                        api_show.name,
+                        file.display()
+                    );
+                    return;
+                }
+            }
+        }
+    };
+
+    // Episode titles are a nice to have, the episode is still imported if the season is unknown.
+    let api_episode = ctx.provider.season(api_show.id, season)
+        .ok()
+        .and_then(|s| s.episodes.into_iter().find(|e| e.episode_number == episodes[0]));
+    let title = api_episode.as_ref().map(|e| e.name.as_str());
+
+    let release = parse::movie::parse_release(file.stem().unwrap_or(""));
+    let duplicates = ctx.db.episode_duplicates(api_show.id, season, &episodes);
+    let copies: Vec<Quality> = duplicates
+        .iter()
+        .map(|e| Quality::of_file(&e.path, &e.release))
+        .collect();
+    let known = release.has_quality() && duplicates.iter().all(|e| e.release.has_quality());
+    let quality = Quality::of_file(file.path(), &release);
+    let decision = decide(ctx.config.tv.duplicates, &copies, quality, known);
+    let mut replaced_files = vec![];
+    let duplicate_index = match decision {
+        Decision::Skip => {
+            println!(
+                "{} is not better than the copy in the library, skipping it.",
+                file.display()
+            );
+            return;
+        }
+        Decision::Replace => {
+            let replaced = ctx.db.episode_duplicates(api_show.id, season, &episodes);
+            for episode in &replaced {
+                replaced_files.push(episode.path.clone());
+                replaced_files.extend(subtitle_files(&episode.subtitles));
+            }
+            replaced.iter().map(|e| e.duplicate_index).min().unwrap_or(1)
+        }
+        Decision::Keep => {
+            ctx.db.episode_duplicates(api_show.id, season, &episodes)
+                .last()
+                .map(|e| e.duplicate_index)
+                .unwrap_or(0) + 1
+        }
+    };
+    let sub_entries: Vec<&Entry> = scan_subtitles(tree, node)
+        .iter()
+        .map(|&n| tree.data(n))
+        .collect();
+
+    let base = ctx.config.tv_path().to_owned();
+    let mut values = episode_values(
+        &api_show,
+        ctx.config.title,
+        season,
+        &episodes,
+        title,
+        file.stem().unwrap_or(""),
+        duplicate_index,
+    );
+    let path = build_path(&ctx.episode_template, &base, &mut values, ext);
+    let convert = ctx.config.subtitles.convert;
+    let subtitles = build_subtitles(
+        &ctx.episode_template,
+        &base,
+        &mut values,
+        file.path(),
+        &sub_entries,
+        convert,
+    );
+
+    let operation = Operation {
+        source: file.path().to_owned(),
+        destination: path.clone(),
+        title: format!(
+            "{} {}",
+            api_show.name,
+            format_episode_numbers(season, &episodes)
+        ),
+        tmdb_id: api_show.id,
+        duplicate_index: duplicate_index,
+        subtitles: transfers(&sub_entries, &subtitles),
+        replaced: trash_transfers(&ctx.config.tv, replaced_files),
+    };
+    if !apply(ctx, operation) {
+        return;
+    }
+    if decision == Decision::Replace {
+        ctx.db.retain_episodes(|e| {
+            e.show_id != api_show.id || e.season != season || e.episodes != episodes
+        });
+    }
+
+    // The show's directory is the first directory of the template.
+    let mut show_path = base.clone();
+    if let Some(component) = path.strip_prefix(&base).ok().and_then(|p| p.iter().next()) {
+        show_path.push(component);
+    }
+    ctx.db.add_show(database::Show {
+        tmdb_id: api_show.id,
+        title: api_show.name.clone(),
+        original_title: api_show.original_name.clone(),
+        year: api_show.year(),
+        overview: api_show.overview.clone(),
+        path: show_path,
+    });
+    ctx.db.add_episode(database::Episode {
+        show_id: api_show.id,
+        season: season,
+        episodes: episodes,
+        duplicate_index: duplicate_index,
+        fingerprint: hash,
+        title: api_episode.as_ref().map(|e| e.name.clone()),
+        overview: api_episode.map(|e| e.overview).unwrap_or_default(),
+        path: path,
+        subtitles: subtitles,
+        release: release,
+    });
+}
+
+/// What happens to a copy of a movie or an episode that is already in the library.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Decision {
+    /// Import it alongside the copies in the library.
+    Keep,
+    /// Import it in place of the copies in the library, which are moved to the trash.
+    Replace,
+    Skip,
+}
+
+/// Decide what happens to a copy of the given quality, `copies` are the qualities of the copies
+/// in the library. Unless the releases of every copy are known, only what is not from the names
+/// of the releases is compared, copies imported before releases were recorded have no source.
+fn decide(policy: Duplicates, copies: &[Quality], quality: Quality, known: bool) -> Decision {
+    let comparable = |quality: Quality| {
+        if known {
+            quality
+        } else {
+            quality.without_release()
+        }
+    };
+    if copies.is_empty() || policy == Duplicates::All {
+        Decision::Keep
+    } else if copies.iter().all(|&copy| comparable(quality) > comparable(copy)) {
+        Decision::Replace
+    } else if policy == Duplicates::Best {
+        Decision::Skip
+    } else {
+        Decision::Keep
+    }
+}
+
+/// Read the headers of the video, `None` if its format is not supported or if they cannot be
+/// read.
+fn read_media(path: &Path) -> Option<MediaInfo> {
+    match container::read(path) {
+        Ok(info) => Some(info),
+        Err(error::Error::Unsupported(_)) => None,
+        Err(err) => {
+            println!("Unable to read the headers of {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Files of the subtitles, along with the `.sub` files of VobSub pairs.
+fn subtitle_files(subtitles: &[Subtitle]) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for sub in subtitles {
+        files.push(sub.path.clone());
+        files.extend(subtitle::companion(&sub.path));
+    }
+    files
+}
+
+/// Moves of the files of replaced copies to the trash of the library, where they keep their
+/// place in the library.
+fn trash_transfers(library: &Library, files: Vec<PathBuf>) -> Vec<Transfer> {
+    let (base, trash) = match (library.path.as_ref(), library.trash_path()) {
+        (Some(base), Some(trash)) => (base, trash),
+        _ => return vec![],
+    };
+    files
+        .into_iter()
+        .map(|source| Transfer {
+            destination: trash.join(source.strip_prefix(base).unwrap_or(&source)),
+            source,
+            convert: None,
+        })
+        .collect()
+}
+
+/// Record a file that could not be imported because of an error, such as a failed lookup.
+fn fail(ctx: &mut Context, path: &Path, err: error::Error) {
+    println!("Unable to import {}: {}", path.display(), err);
+    ctx.report.failures.push(Failure {
+        path: path.to_owned(),
+        message: err.to_string(),
+    });
+}
+
+/// Transfers of the subtitle files, along with the `.sub` files of VobSub pairs. Subtitles whose
+/// format changes are converted.
+fn transfers(entries: &[&Entry], subtitles: &[Subtitle]) -> Vec<Transfer> {
+    let mut transfers = vec![];
+    for (entry, sub) in entries.iter().zip(subtitles) {
+        let from = subtitle::Format::from_path(entry.path());
+        let to = subtitle::Format::from_path(&sub.path);
+        transfers.push(Transfer {
+            source: entry.path().to_owned(),
+            destination: sub.path.clone(),
+            convert: if from != to { to } else { None },
+        });
+        if let Some(companion) = subtitle::companion(entry.path()) {
+            transfers.push(Transfer {
+                source: companion,
+                destination: sub.path.with_extension("sub"),
+                convert: None,
+            });
+        }
+    }
+    transfers
+}
+
+/// Move the files back from the trash.
+fn restore(files: &[Transfer]) {
+    for file in files {
+        let _ = fs::transfer(&file.destination, &file.source, fs::TransferMode::Move);
+    }
+}
+
+/// Move the files to the trash, or move them back if one of them cannot be.
+fn trash(files: &[Transfer]) -> Result<(), error::Error> {
+    for (idx, old) in files.iter().enumerate() {
+        if let Err(err) = fs::transfer(&old.source, &old.destination, fs::TransferMode::Move) {
+            restore(&files[..idx]);
+            return Err(err.into());
+        }
+    }
+    for old in files {
+        println!(
+            "Moved {} to the trash at {}",
+            old.source.display(),
+            old.destination.display()
+        );
+    }
+    Ok(())
+}
+
+/// Transfer the video to the library. A copy replacing others goes next to them first, they are
+/// then moved to the trash before it takes their place. If one of them cannot be moved, the
+/// replacement is abandoned and the library is left as it was.
+fn transfer_video(operation: &Operation, mode: fs::TransferMode) -> Result<(), error::Error> {
+    if operation.replaced.is_empty() {
+        fs::transfer(&operation.source, &operation.destination, mode)?;
+        return Ok(());
+    }
+    let mut staged = operation.destination.clone().into_os_string();
+    staged.push(".part");
+    let staged = PathBuf::from(staged);
+    fs::transfer(&operation.source, &staged, mode)?;
+    let mut result = trash(&operation.replaced);
+    if result.is_ok() {
+        result = ::std::fs::rename(&staged, &operation.destination).map_err(error::Error::from);
+        if result.is_err() {
+            restore(&operation.replaced);
+        }
+    }
+    if result.is_err() {
+        // A moved video is put back in the import directory.
+        let _ = if mode == fs::TransferMode::Move {
+            fs::transfer(&staged, &operation.source, mode)
+        } else {
+            ::std::fs::remove_file(&staged)
+        };
+    }
+    result
+}
+
+/// Transfer the files of the operation, unless this is a dry run. Returns whether the video is
+/// in the library, or would be.
+fn apply(ctx: &mut Context, operation: Operation) -> bool {
+    if !ctx.options.dry_run {
+        let mode = ctx.config.transfer;
+        if let Err(err) = transfer_video(&operation, mode) {
+            fail(ctx, &operation.source, err);
+            return false;
+        }
+        for sub in &operation.subtitles {
+            match sub.convert {
+                Some(format) => {
+                    if let Err(err) = subtitle::convert(&sub.source, &sub.destination, format) {
+                        println!("Unable to convert {}: {}", sub.source.display(), err);
+                    } else if mode == fs::TransferMode::Move {
+                        if let Err(err) = ::std::fs::remove_file(&sub.source) {
+                            println!("Unable to remove {}: {}", sub.source.display(), err);
+                        }
+                    }
+                }
+                None => {
+                    if let Err(err) = fs::transfer(&sub.source, &sub.destination, mode) {
+                        println!("Unable to transfer {}: {}", sub.source.display(), err);
+                    }
+                }
+            }
+        }
+        println!(
+            "Added {} to database at {}",
+            operation.source.display(),
+            operation.destination.display()
         );
     }
+    ctx.report.operations.push(operation);
+    true
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub matching: matching::Options,
+    /// Plan the operations without transferring any file. The database is still updated in memory
+    /// so that duplicates are numbered correctly, it should not be saved.
+    pub dry_run: bool,
+}
+
+/// A file transferred to the library.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transfer {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Format the file is converted to, instead of being transferred as is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub convert: Option<subtitle::Format>,
 }
 
-pub fn import<A>(path: A, db: &mut Database)
+/// A video file transferred to the library, along with its subtitles.
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Title of the movie or episode matched.
+    pub title: String,
+    pub tmdb_id: i64,
+    pub duplicate_index: i32,
+    pub subtitles: Vec<Transfer>,
+    /// Files of the copies replaced by this one, moved to the trash.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replaced: Vec<Transfer>,
+}
+
+/// A file that could not be imported because of an error.
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Operations performed, or planned in a dry run, files that need to be reviewed because they
+/// could not be matched with enough confidence and files that failed to be imported.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Report {
+    pub operations: Vec<Operation>,
+    pub reviews: Vec<Review>,
+    pub failures: Vec<Failure>,
+}
+
+/// State shared while importing the files of a directory.
+struct Context<'a> {
+    tree: &'a Tree<Entry>,
+    db: &'a mut Database,
+    config: &'a Config,
+    provider: &'a MetadataProvider,
+    options: &'a Options,
+    movie_template: Template,
+    episode_template: Template,
+    report: Report,
+}
+
+/// Import the videos found in the directory.
+///
+/// Fails if the naming templates of the libraries are invalid or if the metadata provider
+/// cannot be used.
+pub fn import<A>(
+    path: A,
+    db: &mut Database,
+    config: &Config,
+    provider: &MetadataProvider,
+    options: &Options,
+) -> Result<Report, error::Error>
 where
     A: AsRef<Path>,
 {
-    let (tree, root) = fs::walk(path).expect("failed to walk directory");
-    for node in tree.recursive_iter(root) {
-        let entry = tree.data(node);
-        if entry.is_file() && entry.is_video() {
-            if let (Some(stem), Some(ext)) = (entry.stem(), entry.extension()) {
-                process_video_file(&tree, node, entry, stem, ext, db);
+    provider.verify()?;
+    let movie_template = config.movie_template()?;
+    let episode_template = config.episode_template()?;
+
+    let (tree, root) = fs::walk_ignoring(path, &config.ignore).expect("failed to walk directory");
+    let kinds = classify::classify_all(&tree, root);
+
+    let mut ctx = Context {
+        tree: &tree,
+        db,
+        config,
+        provider,
+        options,
+        movie_template,
+        episode_template,
+        report: Report::default(),
+    };
+    for (subtree, kind) in kinds {
+        println!("Found {} in {}", kind, tree.data(subtree).display());
+        for node in tree.recursive_iter(subtree) {
+            let entry = tree.data(node);
+            if entry.is_file() && entry.is_video() {
+                if let (Some(stem), Some(ext)) = (entry.stem(), entry.extension()) {
+                    let info = match kind {
+                        Kind::Movie => None,
+                        _ => classify::episode_info(&tree, node),
+                    };
+                    match info {
+                        Some(info) => process_episode_file(&mut ctx, node, &info, ext),
+                        None => process_movie_file(&mut ctx, node, stem, ext),
+                    }
+                }
             }
         }
     }
+    Ok(ctx.report)
 }
 
 pub fn scan_subtitles(tree: &Tree<Entry>, video: Node) -> Vec<Node> {
@@ -151,7 +869,9 @@ pub fn scan_subtitles(tree: &Tree<Entry>, video: Node) -> Vec<Node> {
                     let entry = tree.data(n);
                     if entry.is_subtitle() {
                         if let Some(sub_stem) = entry.stem() {
-                            return stem == sub_stem.to_lowercase();
+                            // Also with tags, such as `Movie.en.srt` for `Movie.mkv`.
+                            let sub_stem = sub_stem.to_lowercase();
+                            return sub_stem == stem || sub_stem.starts_with(&format!("{}.", stem));
                         }
                     }
                     false
@@ -161,5 +881,312 @@ pub fn scan_subtitles(tree: &Tree<Entry>, video: Node) -> Vec<Node> {
         }
     }
 
+    // The `.sub` files of VobSub pairs go along with their `.idx` files.
+    subtitles.retain(|&n| !subtitle::is_companion(tree.data(n).path()));
     subtitles
 }
+
+/// NFO files describing the video, every NFO file of its directory if it is the only video in
+/// there, otherwise the NFO file with the same name.
+fn scan_nfo(tree: &Tree<Entry>, video: Node) -> Vec<Node> {
+    let only_video = !tree.siblings(video).any(|n| tree.data(n).is_video());
+    let stem = tree.data(video).stem().map(str::to_lowercase);
+    tree.siblings(video)
+        .filter(|&n| {
+            let entry = tree.data(n);
+            entry.is_file() && entry.is_nfo()
+                && (only_video || entry.stem().map(str::to_lowercase) == stem)
+        })
+        .collect()
+}
+
+/// Find the id of the video in the names of the video and of the directories above it, up to
+/// `levels` names. Movies use their directory, not the pack they may be in, episodes also use the
+/// directory above their season directory.
+fn filename_id(tree: &Tree<Entry>, video: Node, levels: usize) -> Option<ExternalId> {
+    let mut node = Some(video);
+    for _ in 0..levels {
+        let current = node?;
+        let name = tree.data(current).file_name().map(|n| n.to_string_lossy());
+        if let Some(id) = name.and_then(|name| ids::find_in_filename(&name)) {
+            return Some(id);
+        }
+        node = tree.parent(current);
+    }
+    None
+}
+
+/// What the first NFO file of the video with something useful in it says about the video.
+fn read_nfo(tree: &Tree<Entry>, video: Node) -> nfo::Info {
+    for node in scan_nfo(tree, video) {
+        let path = tree.data(node).path();
+        match nfo::read(path) {
+            Ok(ref info) if *info == nfo::Info::default() => {}
+            Ok(info) => return info,
+            Err(err) => println!("Unable to read {}: {}", path.display(), err),
+        }
+    }
+    nfo::Info::default()
+}
+
+pub fn print_plan(operations: &[Operation]) {
+    println!("");
+    println!("{} planned operation(s):", operations.len());
+    for operation in operations {
+        if operation.duplicate_index > 1 {
+            println!(
+                "    {} (copy {})",
+                operation.title, operation.duplicate_index
+            );
+        } else {
+            println!("    {}", operation.title);
+        }
+        println!("        {}", operation.source.display());
+        println!("        -> {}", operation.destination.display());
+        for old in &operation.replaced {
+            println!("        replaces {}", old.source.display());
+        }
+        for sub in &operation.subtitles {
+            println!("        {}", sub.source.display());
+            match sub.convert {
+                Some(format) => println!(
+                    "        -> {} (converted to {})",
+                    sub.destination.display(),
+                    format.extension()
+                ),
+                None => println!("        -> {}", sub.destination.display()),
+            }
+        }
+    }
+}
+
+pub fn print_failures(failures: &[Failure]) {
+    if failures.is_empty() {
+        return;
+    }
+    println!("");
+    println!("{} file(s) failed to import:", failures.len());
+    for failure in failures {
+        println!("    {} ({})", failure.path.display(), failure.message);
+    }
+}
+
+pub fn print_review_report(reviews: &[Review]) {
+    if reviews.is_empty() {
+        return;
+    }
+    println!("");
+    println!("{} file(s) need review:", reviews.len());
+    for review in reviews {
+        match review.best {
+            Some(ref best) => println!(
+                "    {} (best match: {}, {:.0}%)",
+                review.path.display(),
+                best,
+                review.score * 100.0
+            ),
+            None => println!("    {} (no results)", review.path.display()),
+        }
+    }
+}
+
+#[test]
+fn test_decide() {
+    let quality = |name| Quality::new(&parse::movie::parse_release(name), &[], None);
+    let copies = [quality("Movie.2010.720p.HDTV")];
+    let better = quality("Movie.2010.1080p.BluRay");
+    let worse = quality("Movie.2010.DVDRip");
+    assert_eq!(decide(Duplicates::All, &copies, better, true), Decision::Keep);
+    assert_eq!(decide(Duplicates::Best, &copies, better, true), Decision::Replace);
+    assert_eq!(decide(Duplicates::Best, &copies, worse, true), Decision::Skip);
+    assert_eq!(decide(Duplicates::Best, &[], worse, true), Decision::Keep);
+    assert_eq!(decide(Duplicates::Upgrade, &copies, better, true), Decision::Replace);
+    assert_eq!(decide(Duplicates::Upgrade, &copies, copies[0], true), Decision::Keep);
+
+    // A copy named by the template has no source, the source of the new copy does not count.
+    let copies = [quality("Movie (2010)")];
+    let webrip = quality("Movie.2010.WEBRip");
+    assert_eq!(decide(Duplicates::Best, &copies, webrip, true), Decision::Replace);
+    assert_eq!(decide(Duplicates::Best, &copies, webrip, false), Decision::Skip);
+}
+
+#[test]
+fn test_transfer_video() {
+    use std::fs::{create_dir_all, remove_dir_all, File};
+
+    let dir = ::std::env::temp_dir().join("merovingian-test-replace");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(dir.join("library")).unwrap();
+    File::create(dir.join("new.mkv")).unwrap();
+    File::create(dir.join("library/Movie.mkv")).unwrap();
+    // The trash cannot be created under a file.
+    File::create(dir.join("trash")).unwrap();
+    let mut operation = Operation {
+        source: dir.join("new.mkv"),
+        destination: dir.join("library/Movie.mkv"),
+        title: "Movie (2010)".into(),
+        tmdb_id: 1,
+        duplicate_index: 1,
+        subtitles: vec![],
+        replaced: vec![
+            Transfer {
+                source: dir.join("library/Movie.mkv"),
+                destination: dir.join("trash/Movie.mkv"),
+                convert: None,
+            },
+        ],
+    };
+
+    let mode = fs::TransferMode::Move;
+    assert!(transfer_video(&operation, mode).is_err());
+    assert!(dir.join("new.mkv").exists());
+    assert!(dir.join("library/Movie.mkv").exists());
+    assert!(!dir.join("library/Movie.mkv.part").exists());
+
+    operation.replaced[0].destination = dir.join("library/.trash/Movie.mkv");
+    transfer_video(&operation, mode).unwrap();
+    assert!(!dir.join("new.mkv").exists());
+    assert!(dir.join("library/Movie.mkv").exists());
+    assert!(dir.join("library/.trash/Movie.mkv").exists());
+    remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_movie_values_title() {
+    let movie = search::Movie {
+        id: 194,
+        title: "Amélie".into(),
+        original_title: "Le Fabuleux Destin d'Amélie Poulain".into(),
+        overview: String::new(),
+        release_date: "2001-04-25".into(),
+        poster_path: None,
+        backdrop_path: None,
+        popularity: 0.0,
+    };
+    let values = movie_values(&movie, Title::Localized, "Amelie.2001.1080p", 1);
+    assert_eq!(values["title"], "Amélie");
+    assert_eq!(values["quality"], "1080p");
+    let values = movie_values(&movie, Title::Original, "Amelie.2001.1080p", 1);
+    assert_eq!(values["title"], "Le Fabuleux Destin d'Amélie Poulain");
+}
+
+#[cfg(test)]
+fn test_setup(library: &Path) -> (Config, ::provider::Fixture, Options, Database) {
+    let mut config = Config::default();
+    config.movies.path = Some(library.join("movies"));
+    config.tv.path = Some(library.join("tv"));
+    config.transfer = fs::TransferMode::Copy;
+    let provider = ::provider::Fixture::open("testdata/fixtures/tmdb.json").unwrap();
+    let options = Options {
+        matching: matching::Options {
+            unattended: true,
+            ..Default::default()
+        },
+        dry_run: false,
+    };
+    (config, provider, options, Database::new())
+}
+
+#[test]
+fn test_import_offline() {
+    use std::io::Read;
+
+    let library = ::std::env::temp_dir().join("merovingian-test-import");
+    let _ = ::std::fs::remove_dir_all(&library);
+    let (config, provider, options, mut db) = test_setup(&library);
+
+    let report = import("testdata/The.Matrix.1999", &mut db, &config, &provider, &options).unwrap();
+    assert_eq!(report.operations.len(), 1);
+    assert_eq!(report.operations[0].tmdb_id, 603);
+    assert!(library.join("movies/The Matrix (1999)/The Matrix (1999).mp4").is_file());
+    assert!(library.join("movies/The Matrix (1999)/The Matrix (1999).srt").is_file());
+    assert!(library.join("movies/The Matrix (1999)/The Matrix (1999).fr.srt").is_file());
+    let movie = db.duplicates(603, None)[0];
+    assert_eq!(movie.runtime, Some(136));
+    assert_eq!(movie.certification, Some("R".into()));
+    assert_eq!(movie.collection, Some("The Matrix Collection".into()));
+    let nfo = library.join("movies/The Matrix (1999)/movie.nfo");
+    let mut xml = String::new();
+    ::std::fs::File::open(&nfo).unwrap().read_to_string(&mut xml).unwrap();
+    assert!(xml.contains("<uniqueid type=\"imdb\">tt0133093</uniqueid>"));
+    // The NFO files written are read back.
+    let info = ::nfo::read(&nfo).unwrap();
+    assert_eq!(info.id, Some(ExternalId::Tmdb(603)));
+    assert_eq!(info.title, Some("The Matrix".into()));
+
+    // Only Firefly is in the fixture, the other shows are left for review.
+    let report = import("testdata/tv", &mut db, &config, &provider, &options).unwrap();
+    let mut titles: Vec<&str> = report.operations.iter().map(|o| o.title.as_str()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Firefly S01E01", "Firefly S01E02"]);
+    assert!(
+        library
+            .join("tv/Firefly (2002)/Season 01/Firefly - S01E02 - The Train Job.mkv")
+            .is_file()
+    );
+    assert_eq!(report.reviews.len(), 5);
+    assert!(db.show(1437).is_some());
+
+    // Files already in the library are recognised by their fingerprint.
+    let report = import("testdata/The.Matrix.1999", &mut db, &config, &provider, &options).unwrap();
+    assert!(report.operations.is_empty());
+
+    ::std::fs::remove_dir_all(&library).unwrap();
+}
+
+#[test]
+fn test_import_by_id() {
+    let (config, provider, mut options, mut db) = test_setup(Path::new("/"));
+    options.dry_run = true;
+
+    // The title does not match anything, the IMDb id in the NFO file does.
+    let report = import("testdata/ids", &mut db, &config, &provider, &options).unwrap();
+    assert_eq!(report.operations.len(), 1);
+    assert_eq!(report.operations[0].tmdb_id, 603);
+    assert!(report.reviews.is_empty());
+
+    // The title and year in the NFO file are searched instead of the filename.
+    let report = import("testdata/nfo", &mut db, &config, &provider, &options).unwrap();
+    assert_eq!(report.operations.len(), 1);
+    assert_eq!(report.operations[0].tmdb_id, 603);
+}
+
+#[test]
+fn test_filename_id() {
+    let (tree, root) = fs::walk("testdata/packs").unwrap();
+    let video = tree.recursive_iter(root)
+        .find(|&n| tree.data(n).is_file() && tree.data(n).stem() == Some("Reloaded"))
+        .unwrap();
+    // The id of the pack is not the id of every movie in it.
+    assert_eq!(filename_id(&tree, video, 2), None);
+    assert_eq!(
+        filename_id(&tree, video, 3),
+        Some(ExternalId::Imdb("tt0133093".into()))
+    );
+}
+
+#[test]
+fn test_import_subtitles() {
+    let library = ::std::env::temp_dir().join("merovingian-test-subtitles");
+    let _ = ::std::fs::remove_dir_all(&library);
+    let (mut config, provider, options, mut db) = test_setup(&library);
+    config.images.download = false;
+    config.subtitles.convert = Some(subtitle::Format::Vtt);
+
+    let report = import("testdata/subtitles", &mut db, &config, &provider, &options).unwrap();
+    assert_eq!(report.operations.len(), 1);
+    // The `.sub` file of the VobSub pair follows its `.idx` file, the SubRip file is converted.
+    let dir = library.join("movies/The Matrix (1999)");
+    assert!(dir.join("The Matrix (1999).idx").is_file());
+    assert!(dir.join("The Matrix (1999).sub").is_file());
+    let mut vtt = String::new();
+    ::std::fs::File::open(dir.join("The Matrix (1999).en.vtt"))
+        .and_then(|mut file| ::std::io::Read::read_to_string(&mut file, &mut vtt))
+        .unwrap();
+    assert!(vtt.starts_with("WEBVTT\n\n00:00:01.000 --> 00:00:03.500\nWake up, Neo.\n"));
+
+    let movie = db.duplicates(603, None)[0];
+    assert_eq!(movie.subtitles.len(), 2);
+
+    ::std::fs::remove_dir_all(&library).unwrap();
+}
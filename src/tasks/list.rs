@@ -0,0 +1,179 @@
+use container;
+use database::{Database, Movie, TrackKind};
+
+/// Criteria used to filter the movies of the library. Text criteria are case-insensitive and match
+/// part of the value, except for the certification and the language which must be equal.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub director: Option<String>,
+    pub actor: Option<String>,
+    pub certification: Option<String>,
+    pub collection: Option<String>,
+    pub language: Option<String>,
+    /// Maximum runtime, in minutes. Movies with an unknown runtime do not match.
+    pub max_runtime: Option<i32>,
+    /// Language that the movie has neither audio nor subtitles in, such as `fr` or `fre`.
+    pub missing_language: Option<String>,
+}
+
+fn contains(text: &str, part: &str) -> bool {
+    text.to_lowercase().contains(&part.to_lowercase())
+}
+
+fn any_contains(texts: &[String], part: &str) -> bool {
+    texts.iter().any(|text| contains(text, part))
+}
+
+/// Whether the movie has audio or subtitles in the language, embedded or not.
+fn has_language(movie: &Movie, lang: &str) -> bool {
+    let lang = match container::language(lang) {
+        Some(lang) => lang,
+        None => return false,
+    };
+    let tracks = movie
+        .tracks
+        .iter()
+        .filter(|t| t.kind != TrackKind::Video)
+        .map(|t| &t.lang);
+    let subtitles = movie.subtitles.iter().map(|s| &s.lang);
+    tracks
+        .chain(subtitles)
+        .any(|l| l.as_ref().map_or(false, |l| *l == lang))
+}
+
+impl Filter {
+    pub fn matches(&self, movie: &Movie) -> bool {
+        let checks = [
+            self.title.as_ref().map_or(true, |t| {
+                contains(&movie.title, t) || contains(&movie.original_title, t)
+            }),
+            self.year.map_or(true, |y| movie.year == y),
+            self.genre.as_ref().map_or(true, |g| any_contains(&movie.genres, g)),
+            self.director.as_ref().map_or(true, |d| any_contains(&movie.directors, d)),
+            self.actor.as_ref().map_or(true, |a| any_contains(&movie.cast, a)),
+            self.certification.as_ref().map_or(true, |c| {
+                movie.certification.as_ref().map_or(false, |mc| mc.eq_ignore_ascii_case(c))
+            }),
+            self.collection.as_ref().map_or(true, |c| {
+                movie.collection.as_ref().map_or(false, |mc| contains(mc, c))
+            }),
+            self.language.as_ref().map_or(true, |l| {
+                movie.languages.iter().any(|ml| ml.eq_ignore_ascii_case(l))
+            }),
+            self.max_runtime.map_or(true, |max| movie.runtime.map_or(false, |r| r <= max)),
+            self.missing_language.as_ref().map_or(true, |l| !has_language(movie, l)),
+        ];
+        checks.iter().all(|&check| check)
+    }
+}
+
+fn describe(movie: &Movie) -> String {
+    let mut text = format!("{} ({})", movie.title, movie.year);
+    if let Some(ref edition) = movie.edition {
+        text.push_str(&format!(" {}", edition));
+    }
+    if let Some(ref certification) = movie.certification {
+        text.push_str(&format!(" [{}]", certification));
+    }
+    if let Some(runtime) = movie.runtime {
+        text.push_str(&format!(" {} min", runtime));
+    }
+    if !movie.genres.is_empty() {
+        text.push_str(&format!(" - {}", movie.genres.join(", ")));
+    }
+    text
+}
+
+/// Print the movies of the library matching the filter, sorted by title.
+pub fn list(db: &Database, filter: &Filter) {
+    let mut movies: Vec<&Movie> = db.movies().iter().filter(|m| filter.matches(m)).collect();
+    movies.sort_by(|a, b| (&a.title, a.year).cmp(&(&b.title, b.year)));
+    for movie in &movies {
+        println!("{}", describe(movie));
+        if !movie.directors.is_empty() {
+            println!("    Directed by {}", movie.directors.join(", "));
+        }
+        for mismatch in &movie.mismatches {
+            println!("    Warning: {}", mismatch);
+        }
+    }
+    println!("{} movie(s)", movies.len());
+}
+
+#[test]
+fn test_filter() {
+    let movie = Movie {
+        title: "The Matrix".into(),
+        original_title: "The Matrix".into(),
+        year: 1999,
+        runtime: Some(136),
+        certification: Some("R".into()),
+        genres: vec!["Action".into(), "Science Fiction".into()],
+        languages: vec!["en".into()],
+        directors: vec!["Lilly Wachowski".into(), "Lana Wachowski".into()],
+        cast: vec!["Keanu Reeves".into()],
+        ..Movie::default()
+    };
+    assert!(Filter::default().matches(&movie));
+
+    let filter = Filter {
+        genre: Some("science".into()),
+        director: Some("wachowski".into()),
+        certification: Some("r".into()),
+        language: Some("EN".into()),
+        ..Filter::default()
+    };
+    assert!(filter.matches(&movie));
+
+    let filter = Filter {
+        max_runtime: Some(120),
+        ..Filter::default()
+    };
+    assert!(!filter.matches(&movie));
+
+    let filter = Filter {
+        collection: Some("matrix".into()),
+        ..Filter::default()
+    };
+    assert!(!filter.matches(&movie));
+}
+
+#[test]
+fn test_filter_missing_language() {
+    use database::{Subtitle, Track};
+
+    let mut movie = Movie {
+        tracks: vec![
+            Track {
+                kind: TrackKind::Audio,
+                codec: "A_AC3".into(),
+                lang: Some("en".into()),
+                name: None,
+                default: true,
+                forced: false,
+                width: None,
+                height: None,
+                hdr: None,
+                sdr: false,
+                channels: Some(6),
+            },
+        ],
+        ..Movie::default()
+    };
+    let filter = Filter {
+        missing_language: Some("fre".into()),
+        ..Filter::default()
+    };
+    assert!(filter.matches(&movie));
+
+    movie.subtitles.push(Subtitle {
+        lang: Some("fr".into()),
+        forced: false,
+        sdh: false,
+        path: "/movies/The Matrix (1999)/The Matrix (1999).fr.srt".into(),
+    });
+    assert!(!filter.matches(&movie));
+}
@@ -1,2 +1,8 @@
+pub mod cache;
+pub mod images;
 pub mod import;
+pub mod list;
+pub mod nfo;
 pub mod sync;
+pub mod template;
+pub mod tracks;
@@ -0,0 +1,21 @@
+use database::Database;
+use nfo;
+
+/// Write the NFO files of the movies of the library. Existing files are kept unless `force` is
+/// true.
+pub fn regenerate(db: &Database, force: bool) {
+    let mut count = 0;
+    for movie in db.movies() {
+        if !movie.path.exists() {
+            continue;
+        }
+        if nfo::movie_path(&movie.path).exists() && !force {
+            continue;
+        }
+        match nfo::write_movie(movie) {
+            Ok(_) => count += 1,
+            Err(err) => println!("Unable to write the NFO file of {}: {}", movie.title, err),
+        }
+    }
+    println!("Wrote the NFO files of {} movie(s).", count);
+}
@@ -10,5 +10,15 @@ pub fn sync(db: &mut Database) {
             );
         }
         exists
+    });
+    db.retain_episodes(|episode| {
+        let exists = episode.path.exists();
+        if !exists {
+            println!(
+                "{} is missing, removing from database.",
+                episode.path.display()
+            );
+        }
+        exists
     })
 }
@@ -0,0 +1,44 @@
+use config::Config;
+use template::{self, Template};
+
+/// Show the naming template of the library, or replace it if a new template is given.
+pub fn template(config: &mut Config, library: &str, new_template: Option<&str>) {
+    let (current, fields) = match library {
+        "movies" => (config.movie_template(), template::MOVIE_FIELDS),
+        "tv" => (config.episode_template(), template::EPISODE_FIELDS),
+        _ => {
+            println!("Unknown library {}, use movies or tv.", library);
+            return;
+        }
+    };
+
+    let new_template = match new_template {
+        Some(new_template) => new_template,
+        None => {
+            match current {
+                Ok(current) => println!("{}", current.source()),
+                Err(err) => println!("{}", err),
+            }
+            println!("Available fields: {}", fields.join(", "));
+            return;
+        }
+    };
+
+    let parsed = match library {
+        "movies" => Template::movie(new_template),
+        _ => Template::episode(new_template),
+    };
+    match parsed {
+        Ok(parsed) => {
+            let source = Some(parsed.source().to_owned());
+            if library == "movies" {
+                config.movies.template = source;
+            } else {
+                config.tv.template = source;
+            }
+            config.save().expect("unable to save configuration");
+            println!("The {} template is now {}", library, parsed.source());
+        }
+        Err(err) => println!("{}", err),
+    }
+}
@@ -0,0 +1,36 @@
+use container;
+use database::Database;
+use error;
+use parse::movie;
+use parse::release::ReleaseInfo;
+
+/// Read the technical information of the movies of the library that have none, or of every movie
+/// if `force` is true. The filenames of the library are checked against it.
+pub fn scan(db: &mut Database, force: bool) {
+    let mut count = 0;
+    for movie in db.movies_mut() {
+        let scanned = !movie.tracks.is_empty() || movie.duration.is_some();
+        if (scanned && !force) || !movie.path.exists() {
+            continue;
+        }
+        let path = movie.path.clone();
+        match container::read(&path) {
+            Ok(info) => {
+                // Movies imported before the release was recorded only have their name in the
+                // library.
+                if movie.release == ReleaseInfo::default() {
+                    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+                    movie.release = movie::parse_release(&stem.unwrap_or_default());
+                }
+                container::update_movie(movie, info);
+                for mismatch in &movie.mismatches {
+                    println!("Warning: {}: {}", path.display(), mismatch);
+                }
+                count += 1;
+            }
+            Err(error::Error::Unsupported(_)) => {}
+            Err(err) => println!("Unable to read the headers of {}: {}", movie.title, err),
+        }
+    }
+    println!("Read the headers of {} movie(s).", count);
+}
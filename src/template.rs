@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use fs;
+
+pub static DEFAULT_MOVIE_TEMPLATE: &'static str =
+    "{title} ({year})/{title} ({year}) {edition_tag}{version}.{ext}";
+pub static DEFAULT_EPISODE_TEMPLATE: &'static str = concat!(
+    "{show} ({show_year})/{season_dir}/",
+    "{show} - S{season}E{episode} - {episode_title}{version}.{ext}"
+);
+
+/// Fields available in movie templates.
+pub static MOVIE_FIELDS: &'static [&'static str] = &[
+    "title",
+    "original_title",
+    "year",
+    "tmdb_id",
+    "edition",
+    "edition_tag",
+    "quality",
+    "source",
+    "codec",
+    "audio",
+    "hdr",
+    "group",
+    "n",
+    "version",
+    "ext",
+];
+
+/// Fields available in episode templates.
+pub static EPISODE_FIELDS: &'static [&'static str] = &[
+    "show",
+    "original_show",
+    "show_year",
+    "show_tmdb_id",
+    "season",
+    "season_dir",
+    "episode",
+    "episode_title",
+    "quality",
+    "source",
+    "codec",
+    "audio",
+    "hdr",
+    "group",
+    "n",
+    "version",
+    "ext",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub template: String,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid template '{}': {}", self.template, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+/// Naming template for files in the library, such as `{title} ({year})/{title}{version}.{ext}`.
+///
+/// Fields are written between braces, `/` separates directories. Once rendered, empty brackets
+/// and dangling separators left by empty fields are removed, so `{title} [{quality}]` renders as
+/// `Title` when the quality is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+    components: Vec<Vec<Part>>,
+}
+
+impl Template {
+    /// Parse the template, checking that the fields used are in the list of fields given.
+    pub fn parse(source: &str, fields: &[&str]) -> Result<Template, Error> {
+        let error = |message: String| Error {
+            template: source.into(),
+            message,
+        };
+
+        let mut components = vec![];
+        for component in source.split('/') {
+            let mut parts = vec![];
+            let mut rest = component;
+            while let Some(start) = rest.find('{') {
+                if start > 0 {
+                    parts.push(Part::Literal(rest[..start].into()));
+                }
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => return Err(error("unclosed brace".into())),
+                };
+                let field = &rest[start + 1..end];
+                if !fields.contains(&field) {
+                    return Err(error(format!("unknown field '{}'", field)));
+                }
+                parts.push(Part::Field(field.into()));
+                rest = &rest[end + 1..];
+            }
+            if rest.contains('}') {
+                return Err(error("unopened brace".into()));
+            }
+            if !rest.is_empty() {
+                parts.push(Part::Literal(rest.into()));
+            }
+            if parts.is_empty() {
+                return Err(error("empty directory name".into()));
+            }
+            components.push(parts);
+        }
+
+        Ok(Template {
+            source: source.into(),
+            components,
+        })
+    }
+
+    /// Parse a movie template and check that it produces unique paths.
+    ///
+    /// A movie is identified by its TMDB id or its title and year, remakes often have the same
+    /// title. The filename must contain the duplicate index and end with the extension. Editions
+    /// without `{edition}` or `{edition_tag}` are numbered like duplicates.
+    pub fn movie(source: &str) -> Result<Template, Error> {
+        let template = Template::parse(source, MOVIE_FIELDS)?;
+        template.require(&[&["tmdb_id"], &["title", "year"], &["original_title", "year"]])?;
+        template.require_filename()?;
+        Ok(template)
+    }
+
+    /// Parse an episode template and check that it produces unique paths.
+    ///
+    /// An episode is identified by its show, season and episode numbers. The filename must
+    /// contain the duplicate index and end with the extension.
+    pub fn episode(source: &str) -> Result<Template, Error> {
+        let template = Template::parse(source, EPISODE_FIELDS)?;
+        template.require(&[&["show_tmdb_id"], &["show"], &["original_show"]])?;
+        template.require(&[&["season"], &["season_dir"]])?;
+        template.require(&[&["episode"]])?;
+        template.require_filename()?;
+        Ok(template)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn uses(&self, field: &str) -> bool {
+        self.fields().any(|f| f == field)
+    }
+
+    fn fields<'t>(&'t self) -> impl Iterator<Item = &'t str> + 't {
+        self.components.iter().flat_map(|parts| {
+            parts.iter().filter_map(|part| match *part {
+                Part::Field(ref field) => Some(field.as_str()),
+                _ => None,
+            })
+        })
+    }
+
+    /// Check that at least one of the sets of fields is entirely used by the template.
+    fn require(&self, alternatives: &[&[&str]]) -> Result<(), Error> {
+        let fields: Vec<&str> = self.fields().collect();
+        if alternatives
+            .iter()
+            .any(|set| set.iter().all(|f| fields.contains(f)))
+        {
+            return Ok(());
+        }
+        let names: Vec<String> = alternatives
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|f| format!("{{{}}}", f))
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            })
+            .collect();
+        Err(Error {
+            template: self.source.clone(),
+            message: format!("paths are not unique, use {}", names.join(" or ")),
+        })
+    }
+
+    fn require_filename(&self) -> Result<(), Error> {
+        let filename = &self.components[self.components.len() - 1];
+        let error = |message: &str| Error {
+            template: self.source.clone(),
+            message: message.into(),
+        };
+        if filename.last() != Some(&Part::Field("ext".into())) {
+            return Err(error("the filename must end with {ext}"));
+        }
+        if !filename
+            .iter()
+            .any(|p| *p == Part::Field("version".into()) || *p == Part::Field("n".into()))
+        {
+            return Err(error(
+                "duplicates are not unique, use {version} or {n} in the filename",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Render the template inside of the base directory. Missing fields are rendered as empty.
+    pub fn render(&self, base: &Path, values: &HashMap<&str, String>) -> PathBuf {
+        let mut path = base.to_owned();
+        for parts in &self.components {
+            let mut text = String::new();
+            for part in parts {
+                match *part {
+                    Part::Literal(ref literal) => text.push_str(literal),
+                    Part::Field(ref field) => {
+                        if let Some(value) = values.get(field.as_str()) {
+                            text.push_str(value);
+                        }
+                    }
+                }
+            }
+            path.push(fs::filter_filename(&tidy(&text)));
+        }
+        path
+    }
+}
+
+/// Remove the empty brackets and dangling separators left by empty fields.
+fn tidy(text: &str) -> String {
+    let mut text = text.to_owned();
+    loop {
+        let before = text.len();
+        for &(from, to) in &[
+            ("()", ""),
+            ("[]", ""),
+            ("  ", " "),
+            (" - - ", " - "),
+            (" - .", "."),
+            (" .", "."),
+        ] {
+            text = text.replace(from, to);
+        }
+        if text.len() == before {
+            break;
+        }
+    }
+    text.trim_matches(|c| c == ' ' || c == '-').into()
+}
+
+#[test]
+fn test_render_movie() {
+    let template = Template::movie("{title} ({year}) [{quality}]/{title}{version}.{ext}").unwrap();
+    let values = hashmap!{
+        "title" => "The Matrix".into(),
+        "year" => "1999".into(),
+        "quality" => "1080p".into(),
+        "version" => "".into(),
+        "ext" => "mkv".into(),
+    };
+    assert_eq!(
+        template.render(Path::new("/movies"), &values),
+        Path::new("/movies/The Matrix (1999) [1080p]/The Matrix.mkv")
+    );
+}
+
+#[test]
+fn test_render_edition() {
+    let template = Template::movie(DEFAULT_MOVIE_TEMPLATE).unwrap();
+    let mut values = hashmap!{
+        "title" => "Blade Runner".into(),
+        "year" => "1982".into(),
+        "version" => "".into(),
+        "ext" => "mkv".into(),
+    };
+    assert_eq!(
+        template.render(Path::new("/movies"), &values),
+        Path::new("/movies/Blade Runner (1982)/Blade Runner (1982).mkv")
+    );
+    values.insert("edition_tag", "{edition-Final Cut}".into());
+    assert_eq!(
+        template.render(Path::new("/movies"), &values),
+        Path::new("/movies/Blade Runner (1982)/Blade Runner (1982) {edition-Final Cut}.mkv")
+    );
+}
+
+#[test]
+fn test_render_empty_fields() {
+    let template = Template::episode(DEFAULT_EPISODE_TEMPLATE).unwrap();
+    let values = hashmap!{
+        "show" => "Firefly".into(),
+        "season" => "01".into(),
+        "season_dir" => "Season 01".into(),
+        "episode" => "02".into(),
+        "ext" => "mkv".into(),
+    };
+    assert_eq!(
+        template.render(Path::new("/tv"), &values),
+        Path::new("/tv/Firefly/Season 01/Firefly - S01E02.mkv")
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(Template::parse("{title", MOVIE_FIELDS).is_err());
+    assert!(Template::parse("title}", MOVIE_FIELDS).is_err());
+    assert!(Template::parse("{foo}.{ext}", MOVIE_FIELDS).is_err());
+    assert!(Template::parse("a//{ext}", MOVIE_FIELDS).is_err());
+}
+
+#[test]
+fn test_unique() {
+    assert!(Template::movie(DEFAULT_MOVIE_TEMPLATE).is_ok());
+    assert!(Template::movie("{title}/{title}{version}.{ext}").is_err());
+    assert!(Template::movie("{tmdb_id}/{title}{n}.{ext}").is_ok());
+    assert!(Template::movie("{title} ({year})/{title}.{ext}").is_err());
+    assert!(Template::movie("{title} ({year})/{title}{version}").is_err());
+    assert!(Template::episode("{show}/{show} S{season}{version}.{ext}").is_err());
+}
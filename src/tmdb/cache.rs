@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+
+use error;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+/// Kind of endpoint a cache key belongs to, used to pick its time to live.
+pub fn kind(key: &str) -> &'static str {
+    let path = key.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let first = segments.first().cloned().unwrap_or("");
+    if first == "search" || first == "find" {
+        "search"
+    } else if first == "configuration" {
+        "configuration"
+    } else if segments.len() == 3 && segments[2] == "images" {
+        "images"
+    } else if first == "tv" && segments.get(2) == Some(&"season") {
+        "season"
+    } else if first == "movie" {
+        "movie"
+    } else if first == "tv" {
+        "tv"
+    } else {
+        "other"
+    }
+}
+
+/// How long a response stays fresh. Search results change as titles are added to TMDB, details of
+/// existing titles rarely do.
+pub fn ttl(key: &str) -> Duration {
+    Duration::from_secs(match kind(key) {
+        "search" => DAY,
+        "configuration" => 3 * DAY,
+        "season" => 3 * DAY,
+        "movie" | "tv" | "images" => 7 * DAY,
+        _ => HOUR,
+    })
+}
+
+/// Cache key of a request, the API path followed by the parameters sorted by name.
+///
+/// The credentials must not be part of the parameters, so that the cache survives a change of API
+/// key.
+pub fn key(path: &str, params: &HashMap<&'static str, String>) -> String {
+    let mut pairs: Vec<String> = params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    pairs.sort();
+    if pairs.is_empty() {
+        path.into()
+    } else {
+        format!("{}?{}", path, pairs.join("&"))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A cached response.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Entry {
+    pub key: String,
+    /// When the response was stored, in seconds since the unix epoch.
+    pub stored: u64,
+    pub body: Value,
+}
+
+impl Entry {
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.stored))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.age() > ttl(&self.key)
+    }
+}
+
+/// Persistent cache of the TMDB responses, one JSON file per request.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new<A>(dir: A) -> Cache
+    where
+        A: Into<PathBuf>,
+    {
+        Cache { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn file(&self, key: &str) -> PathBuf {
+        let mut hasher = Sha256::default();
+        hasher.input(key.as_bytes());
+        let mut name = String::with_capacity(69);
+        for byte in &hasher.result()[..] {
+            let _ = write!(name, "{:02x}", byte);
+        }
+        name.push_str(".json");
+        self.dir.join(name)
+    }
+
+    /// Get the entry of the key, even if it expired. Unreadable entries are ignored.
+    pub fn get(&self, key: &str) -> Option<Entry> {
+        let file = File::open(self.file(key)).ok()?;
+        let entry: Entry = serde_json::from_reader(BufReader::new(file)).ok()?;
+        // Protect against hash collisions, however unlikely.
+        if entry.key == key {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    pub fn put(&self, key: &str, body: &Value) -> Result<(), error::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = Entry {
+            key: key.into(),
+            stored: now(),
+            body: body.clone(),
+        };
+        let file = BufWriter::new(File::create(self.file(key))?);
+        serde_json::to_writer(file, &entry)?;
+        Ok(())
+    }
+
+    /// Every readable entry of the cache, along with the size of its file.
+    pub fn entries(&self) -> Result<Vec<(Entry, u64)>, error::Error> {
+        let mut entries = vec![];
+        if !self.dir.exists() {
+            return Ok(entries);
+        }
+        for dirent in fs::read_dir(&self.dir)? {
+            let path = dirent?.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let size = path.metadata()?.len();
+            let file = BufReader::new(File::open(&path)?);
+            if let Ok(entry) = serde_json::from_reader(file) {
+                entries.push((entry, size));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Remove the entries, only the expired ones if `expired_only` is true. Returns the number of
+    /// entries removed.
+    pub fn purge(&self, expired_only: bool) -> Result<usize, error::Error> {
+        let mut count = 0;
+        for (entry, _) in self.entries()? {
+            if !expired_only || entry.is_expired() {
+                fs::remove_file(self.file(&entry.key))?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[test]
+fn test_kind() {
+    assert_eq!(kind("/search/movie?query=the matrix"), "search");
+    assert_eq!(kind("/movie/603"), "movie");
+    assert_eq!(kind("/movie/603/images"), "images");
+    assert_eq!(kind("/tv/1437/season/1"), "season");
+    assert_eq!(kind("/tv/1437"), "tv");
+    assert_eq!(kind("/configuration"), "configuration");
+}
+
+#[test]
+fn test_key() {
+    let params = hashmap!{
+        "year" => "1999".into(),
+        "query" => "the matrix".into(),
+    };
+    assert_eq!(key("/search/movie", &params), "/search/movie?query=the matrix&year=1999");
+    assert_eq!(key("/movie/603", &HashMap::new()), "/movie/603");
+}
+
+#[test]
+fn test_cache() {
+    let dir = ::std::env::temp_dir().join("merovingian-test-cache");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Cache::new(&dir);
+
+    assert!(cache.get("/movie/603").is_none());
+    let body: Value = serde_json::from_str(r#"{"id": 603}"#).unwrap();
+    cache.put("/movie/603", &body).unwrap();
+    let entry = cache.get("/movie/603").unwrap();
+    assert_eq!(entry.body["id"], 603);
+    assert!(!entry.is_expired());
+
+    assert_eq!(cache.purge(true).unwrap(), 0);
+    assert_eq!(cache.purge(false).unwrap(), 1);
+    assert!(cache.get("/movie/603").is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
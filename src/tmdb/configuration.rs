@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use error;
+use tmdb::Client;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Configuration {
+    pub images: ImageConfiguration,
+}
+
+/// Where images are served from and the sizes available.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageConfiguration {
+    pub secure_base_url: String,
+    #[serde(default)]
+    pub poster_sizes: Vec<String>,
+    #[serde(default)]
+    pub backdrop_sizes: Vec<String>,
+}
+
+impl ImageConfiguration {
+    /// URL of the image at the given size, or at its original size if TMDB does not offer that
+    /// size.
+    pub fn url(&self, file_path: &str, size: &str, sizes: &[String]) -> String {
+        let size = if sizes.iter().any(|s| s == size) {
+            size
+        } else {
+            "original"
+        };
+        format!(
+            "{}/{}/{}",
+            self.secure_base_url.trim_right_matches('/'),
+            size,
+            file_path.trim_left_matches('/')
+        )
+    }
+}
+
+pub fn get(client: &Client) -> Result<Configuration, error::Error> {
+    client.get("/configuration", HashMap::new())
+}
+
+#[test]
+fn test_image_url() {
+    let config = ImageConfiguration {
+        secure_base_url: "https://image.tmdb.org/t/p/".into(),
+        poster_sizes: vec!["w342".into(), "w780".into(), "original".into()],
+        backdrop_sizes: vec![],
+    };
+    assert_eq!(
+        config.url("/abc.jpg", "w780", &config.poster_sizes),
+        "https://image.tmdb.org/t/p/w780/abc.jpg"
+    );
+    assert_eq!(
+        config.url("/abc.jpg", "w9000", &config.poster_sizes),
+        "https://image.tmdb.org/t/p/original/abc.jpg"
+    );
+}
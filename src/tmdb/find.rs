@@ -0,0 +1,27 @@
+use error;
+use tmdb::{search, Client};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FoundEpisode {
+    pub show_id: i64,
+    pub season_number: i32,
+    pub episode_number: i32,
+}
+
+/// Movies, shows and episodes with an external id.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Results {
+    #[serde(default)]
+    pub movie_results: Vec<search::Movie>,
+    #[serde(default)]
+    pub tv_results: Vec<search::Show>,
+    #[serde(default)]
+    pub tv_episode_results: Vec<FoundEpisode>,
+}
+
+pub fn imdb(client: &Client, imdb_id: &str) -> Result<Results, error::Error> {
+    let params = hashmap!{
+        "external_source" => "imdb_id".into(),
+    };
+    client.get(&format!("/find/{}", imdb_id), params)
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use error;
+use tmdb::Client;
+
+/// Images of a movie or a show.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Images {
+    #[serde(default)]
+    pub posters: Vec<Image>,
+    #[serde(default)]
+    pub backdrops: Vec<Image>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Image {
+    /// Path of the image, to be appended to a base URL and a size.
+    pub file_path: String,
+    pub width: i32,
+    pub height: i32,
+    /// Language of the text in the image, if there is any.
+    pub iso_639_1: Option<String>,
+    #[serde(default)]
+    pub vote_average: f64,
+}
+
+/// Images are filtered by the language of the client, keep those without text too.
+fn params(client: &Client) -> HashMap<&'static str, String> {
+    let mut params = HashMap::new();
+    if let Some(ref language) = client.language {
+        let language = language.split('-').next().unwrap_or("");
+        params.insert("include_image_language", format!("{},null", language));
+    }
+    params
+}
+
+pub fn movie(client: &Client, id: i64) -> Result<Images, error::Error> {
+    client.get(&format!("/movie/{}/images", id), params(client))
+}
+
+pub fn tv(client: &Client, id: i64) -> Result<Images, error::Error> {
+    client.get(&format!("/tv/{}/images", id), params(client))
+}
@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Token bucket, a request costs one token and tokens are refilled at a constant rate up to the
+/// capacity of the bucket.
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// Tokens refilled per second.
+    rate: f64,
+    /// Tokens available, negative when requests are waiting for tokens.
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: u32, per: Duration, now: Instant) -> Bucket {
+        let secs = per.as_secs() as f64 + f64::from(per.subsec_nanos()) / 1e9;
+        Bucket {
+            capacity: f64::from(capacity),
+            rate: f64::from(capacity) / secs,
+            tokens: f64::from(capacity),
+            last: now,
+        }
+    }
+
+    /// Take a token, returning how long to wait before using it.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.duration_since(self.last);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        self.tokens = f64::min(self.capacity, self.tokens + elapsed * self.rate);
+        self.last = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            let wait = -self.tokens / self.rate;
+            Duration::new(wait as u64, (wait.fract() * 1e9) as u32)
+        }
+    }
+}
+
+/// Limit the rate of requests, shared by every request of a client.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Allow `capacity` requests per period, in bursts of up to `capacity` requests.
+    pub fn new(capacity: u32, per: Duration) -> RateLimiter {
+        RateLimiter {
+            bucket: Mutex::new(Bucket::new(capacity, per, Instant::now())),
+        }
+    }
+
+    /// Wait until a request can be made.
+    pub fn acquire(&self) {
+        let wait = self.bucket.lock().unwrap().take(Instant::now());
+        if wait > Duration::from_secs(0) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[test]
+fn test_bucket() {
+    let start = Instant::now();
+    let mut bucket = Bucket::new(2, Duration::from_secs(1), start);
+    assert_eq!(bucket.take(start), Duration::from_secs(0));
+    assert_eq!(bucket.take(start), Duration::from_secs(0));
+    // The bucket is empty, the next token comes in half a second.
+    assert_eq!(bucket.take(start), Duration::from_millis(500));
+    // The token reserved above is paid for, another one is available after a second.
+    assert_eq!(bucket.take(start + Duration::from_secs(1)), Duration::from_secs(0));
+    // The bucket never holds more than its capacity.
+    let later = start + Duration::from_secs(60);
+    bucket.take(later);
+    bucket.take(later);
+    assert_eq!(bucket.take(later), Duration::from_millis(500));
+}
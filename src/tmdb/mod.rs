@@ -1 +1,347 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::str;
+use std::thread;
+use std::time::Duration;
+
+use reqwest::header::{Authorization, Bearer};
+use reqwest::Response;
+use reqwest::{self, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use config::Config;
+use database::ImageKind;
+use error;
+use provider::{Media, MetadataProvider};
+
+pub mod cache;
+pub mod configuration;
+pub mod find;
+pub mod images;
+pub mod limit;
+pub mod movie;
 pub mod search;
+pub mod tv;
+
+use self::cache::Cache;
+use self::limit::RateLimiter;
+
+static BASE_URL: &'static str = "https://api.themoviedb.org/3";
+/// TMDB allows 40 requests every 10 seconds.
+const RATE_LIMIT: u32 = 40;
+const RATE_PERIOD: u64 = 10;
+const TIMEOUT: u64 = 30;
+/// Number of times a request is retried after a server error, a timeout or being rate limited.
+const MAX_RETRIES: u32 = 5;
+
+/// Credentials used to authenticate with the TMDB API.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    /// v3 API key, sent as the `api_key` parameter.
+    ApiKey(String),
+    /// v4 read access token, sent as a bearer token.
+    Bearer(String),
+}
+
+impl Credentials {
+    /// Guess the kind of credentials. v4 tokens are JSON web tokens, which are much longer than
+    /// the 32 characters of v3 keys and start with the base64 encoding of `{"`.
+    pub fn new(key: &str) -> Credentials {
+        let key = key.trim();
+        if key.starts_with("eyJ") && key.contains('.') {
+            Credentials::Bearer(key.into())
+        } else {
+            Credentials::ApiKey(key.into())
+        }
+    }
+}
+
+pub struct Client {
+    /// Only missing in offline mode, where no request is made.
+    credentials: Option<Credentials>,
+    cache: Option<Cache>,
+    offline: bool,
+    /// Language of the metadata, sent with every request.
+    language: Option<String>,
+    /// Country of the release dates, sent with movie searches.
+    region: Option<String>,
+    /// Shared by every request, so that connections are reused.
+    http: reqwest::Client,
+    limiter: RateLimiter,
+}
+
+/// Delay before retrying a request, doubled after every attempt.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.min(5))
+}
+
+/// Delay requested by the `Retry-After` header, in seconds.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get_raw("Retry-After")
+        .and_then(|raw| raw.one())
+        .and_then(|value| str::from_utf8(value).ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+impl Client {
+    pub fn new(credentials: Credentials) -> Result<Client, error::Error> {
+        Client::with_credentials(Some(credentials))
+    }
+
+    fn with_credentials(credentials: Option<Credentials>) -> Result<Client, error::Error> {
+        Ok(Client {
+            credentials,
+            cache: None,
+            offline: false,
+            language: None,
+            region: None,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT))
+                .build()?,
+            limiter: RateLimiter::new(RATE_LIMIT, Duration::from_secs(RATE_PERIOD)),
+        })
+    }
+
+    /// Create a client with the API key of the configuration, or the `MERO_API_KEY` environment
+    /// variable, and the cache, language and region of the configuration.
+    ///
+    /// In offline mode, responses are only served from the cache, even if they expired, and the API
+    /// key is optional.
+    pub fn from_config(config: &Config, offline: bool) -> Result<Client, error::Error> {
+        let key = env::var("MERO_API_KEY")
+            .ok()
+            .or_else(|| config.api_key.clone())
+            .and_then(|key| if key.trim().is_empty() { None } else { Some(key) });
+        let mut client = match key {
+            Some(key) => Client::new(Credentials::new(&key))?,
+            None if offline => Client::with_credentials(None)?,
+            None => return Err(error::Error::MissingApiKey),
+        };
+        client.cache = Some(Cache::new(config.cache_path()));
+        client.offline = offline;
+        client.language = config.language.clone();
+        client.region = config.region.clone();
+        Ok(client)
+    }
+
+    /// Make sure the credentials are accepted by TMDB. Always succeeds in offline mode.
+    pub fn verify(&self) -> Result<(), error::Error> {
+        if self.offline {
+            return Ok(());
+        }
+        self.fetch("/configuration", HashMap::new()).map(|_| ())
+    }
+
+    /// Download a file to the destination, creating its directory if needed.
+    fn download(&self, url: &str, dest: &Path) -> Result<(), error::Error> {
+        if self.offline {
+            return Err(error::Error::Offline(url.into()));
+        }
+        let mut resp = self.http.get(url).send()?;
+        if resp.status() != StatusCode::Ok {
+            return Err(error::Error::Download(url.into(), resp.status().as_u16()));
+        }
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file, an interrupted download must not look like a complete one.
+        let part = dest.with_extension("part");
+        io::copy(&mut resp, &mut File::create(&part)?)?;
+        fs::rename(&part, dest)?;
+        Ok(())
+    }
+
+    /// Get the response of the given API path from the cache, or from TMDB if the cached response
+    /// is missing or expired.
+    fn get<T>(
+        &self,
+        path: &str,
+        mut params: HashMap<&'static str, String>,
+    ) -> Result<T, error::Error>
+    where
+        T: DeserializeOwned,
+    {
+        if let Some(ref language) = self.language {
+            params.insert("language", language.clone());
+        }
+        let key = cache::key(path, &params);
+        if let Some(ref cache) = self.cache {
+            if let Some(entry) = cache.get(&key) {
+                if self.offline || !entry.is_expired() {
+                    return Ok(serde_json::from_value(entry.body)?);
+                }
+            }
+        }
+        if self.offline {
+            return Err(error::Error::Offline(key));
+        }
+
+        let body = self.fetch(path, params)?;
+        if let Some(ref cache) = self.cache {
+            cache.put(&key, &body)?;
+        }
+        Ok(serde_json::from_value(body)?)
+    }
+
+    /// Perform a GET request on the given API path and decode the JSON response.
+    ///
+    /// Requests are rate limited. They are retried after server errors and timeouts, waiting longer
+    /// after every attempt, and after being rate limited by TMDB, waiting as long as requested.
+    /// Other non-200 responses are decoded as `search::Error`. A 401 response means that the
+    /// credentials were rejected.
+    fn fetch(
+        &self,
+        path: &str,
+        mut params: HashMap<&'static str, String>,
+    ) -> Result<Value, error::Error> {
+        let url = format!("{}{}", BASE_URL, path);
+        let bearer = match self.credentials {
+            Some(Credentials::ApiKey(ref key)) => {
+                params.insert("api_key", key.clone());
+                None
+            }
+            Some(Credentials::Bearer(ref token)) => Some(token),
+            None => return Err(error::Error::MissingApiKey),
+        };
+
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire();
+            let mut builder = self.http.get(&url);
+            if let Some(token) = bearer {
+                builder.header(Authorization(Bearer {
+                    token: token.clone(),
+                }));
+            }
+            let req = builder.query(&params).build()?;
+
+            let (delay, err) = match self.http.execute(req) {
+                Ok(mut resp) => {
+                    let status = resp.status();
+                    if status == StatusCode::Ok {
+                        return Ok(resp.json()?);
+                    }
+                    let delay = if status == StatusCode::TooManyRequests {
+                        Some(retry_after(&resp).unwrap_or_else(|| backoff(attempt)))
+                    } else if status.is_server_error() {
+                        Some(backoff(attempt))
+                    } else {
+                        None
+                    };
+                    let err = if status == StatusCode::Unauthorized {
+                        // The body is not always TMDB's JSON, the status is enough.
+                        let err = resp.json().unwrap_or_else(|_| search::Error {
+                            status_message: "Invalid API key".into(),
+                            status_code: 7,
+                        });
+                        error::Error::ApiKeyRejected(err)
+                    } else {
+                        match resp.json::<search::Error>() {
+                            Ok(err) => err.into(),
+                            Err(err) => err.into(),
+                        }
+                    };
+                    match delay {
+                        Some(delay) => (delay, err),
+                        None => return Err(err),
+                    }
+                }
+                // Connection errors and timeouts.
+                Err(err) => (backoff(attempt), err.into()),
+            };
+
+            if attempt >= MAX_RETRIES {
+                return Err(err);
+            }
+            println!("TMDB request failed ({}), retrying in {}s.", err, delay.as_secs());
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+impl MetadataProvider for Client {
+    fn verify(&self) -> Result<(), error::Error> {
+        Client::verify(self)
+    }
+
+    fn search_movie(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Movie>, error::Error> {
+        search::movie(self, query, year).map(|paged| paged.results)
+    }
+
+    fn movie(&self, id: i64) -> Result<search::Movie, error::Error> {
+        movie::get(self, id)
+    }
+
+    fn movie_details(&self, id: i64) -> Result<movie::Details, error::Error> {
+        movie::details(self, id)
+    }
+
+    fn find_imdb(&self, imdb_id: &str) -> Result<find::Results, error::Error> {
+        find::imdb(self, imdb_id)
+    }
+
+    fn search_show(
+        &self,
+        query: &str,
+        year: Option<i32>,
+    ) -> Result<Vec<search::Show>, error::Error> {
+        search::tv(self, query, year).map(|paged| paged.results)
+    }
+
+    fn show(&self, id: i64) -> Result<search::Show, error::Error> {
+        tv::get(self, id)
+    }
+
+    fn show_details(&self, id: i64) -> Result<tv::Details, error::Error> {
+        tv::details(self, id)
+    }
+
+    fn season(&self, show_id: i64, season: i32) -> Result<tv::Season, error::Error> {
+        tv::season(self, show_id, season)
+    }
+
+    fn download_image(
+        &self,
+        kind: ImageKind,
+        file_path: &str,
+        size: &str,
+        dest: &Path,
+    ) -> Result<(), error::Error> {
+        let images = configuration::get(self)?.images;
+        let sizes = match kind {
+            ImageKind::Poster => &images.poster_sizes,
+            ImageKind::Backdrop => &images.backdrop_sizes,
+        };
+        self.download(&images.url(file_path, size, sizes), dest)
+    }
+
+    fn images(&self, media: Media, id: i64) -> Result<images::Images, error::Error> {
+        match media {
+            Media::Movie => images::movie(self, id),
+            Media::Show => images::tv(self, id),
+        }
+    }
+}
+
+#[test]
+fn test_credentials() {
+    assert_eq!(
+        Credentials::new("0123456789abcdef0123456789abcdef\n"),
+        Credentials::ApiKey("0123456789abcdef0123456789abcdef".into())
+    );
+    assert_eq!(
+        Credentials::new("eyJhbGciOiJIUzI1NiJ9.eyJhdWQiOiIxIn0.c2ln"),
+        Credentials::Bearer("eyJhbGciOiJIUzI1NiJ9.eyJhdWQiOiIxIn0.c2ln".into())
+    );
+}
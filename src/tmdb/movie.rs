@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use error;
+use tmdb::{search, Client};
+
+/// Release type of theatrical releases, see `ReleaseDate`.
+const THEATRICAL: i32 = 3;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Genre {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpokenLanguage {
+    pub iso_639_1: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cast {
+    pub name: String,
+    #[serde(default)]
+    pub character: String,
+    pub order: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Crew {
+    pub name: String,
+    pub job: String,
+    #[serde(default)]
+    pub department: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Credits {
+    #[serde(default)]
+    pub cast: Vec<Cast>,
+    #[serde(default)]
+    pub crew: Vec<Crew>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReleaseDate {
+    #[serde(default)]
+    pub certification: String,
+    /// 1 premiere, 2 limited theatrical, 3 theatrical, 4 digital, 5 physical, 6 tv.
+    #[serde(rename = "type")]
+    pub kind: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CountryReleases {
+    pub iso_3166_1: String,
+    pub release_dates: Vec<ReleaseDate>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReleaseDates {
+    #[serde(default)]
+    pub results: Vec<CountryReleases>,
+}
+
+/// Details of a movie, with its credits and release dates appended.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Details {
+    pub id: i64,
+    pub title: String,
+    pub original_title: String,
+    #[serde(default)]
+    pub overview: String,
+    #[serde(default)]
+    pub tagline: String,
+    pub release_date: String,
+    pub runtime: Option<i32>,
+    pub imdb_id: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+    pub belongs_to_collection: Option<Collection>,
+    #[serde(default)]
+    pub spoken_languages: Vec<SpokenLanguage>,
+    #[serde(default)]
+    pub credits: Credits,
+    #[serde(default)]
+    pub release_dates: ReleaseDates,
+}
+
+impl Details {
+    /// Certification of the movie in the country, such as `PG-13` in `US`. The certification of
+    /// the theatrical release is preferred.
+    pub fn certification(&self, country: &str) -> Option<&str> {
+        let releases = self.release_dates
+            .results
+            .iter()
+            .find(|r| r.iso_3166_1.eq_ignore_ascii_case(country))?;
+        let mut dates: Vec<&ReleaseDate> = releases
+            .release_dates
+            .iter()
+            .filter(|d| !d.certification.is_empty())
+            .collect();
+        dates.sort_by_key(|d| d.kind != THEATRICAL);
+        dates.first().map(|d| d.certification.as_str())
+    }
+
+    pub fn directors(&self) -> Vec<&str> {
+        self.credits
+            .crew
+            .iter()
+            .filter(|c| c.job == "Director")
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    /// Names of the first actors, in billing order.
+    pub fn cast(&self, count: usize) -> Vec<&str> {
+        let mut cast: Vec<&Cast> = self.credits.cast.iter().collect();
+        cast.sort_by_key(|c| c.order);
+        cast.iter().take(count).map(|c| c.name.as_str()).collect()
+    }
+}
+
+pub fn get(client: &Client, id: i64) -> Result<search::Movie, error::Error> {
+    client.get(&format!("/movie/{}", id), HashMap::new())
+}
+
+pub fn details(client: &Client, id: i64) -> Result<Details, error::Error> {
+    let params = hashmap!{
+        "append_to_response" => "credits,release_dates".into(),
+    };
+    client.get(&format!("/movie/{}", id), params)
+}
+
+#[test]
+fn test_certification() {
+    let details: Details = ::serde_json::from_str(
+        r#"{
+            "id": 603,
+            "title": "The Matrix",
+            "original_title": "The Matrix",
+            "release_date": "1999-03-30",
+            "runtime": 136,
+            "imdb_id": "tt0133093",
+            "belongs_to_collection": null,
+            "release_dates": {"results": [
+                {"iso_3166_1": "US", "release_dates": [
+                    {"certification": "", "type": 1},
+                    {"certification": "NR", "type": 5},
+                    {"certification": "R", "type": 3}
+                ]}
+            ]},
+            "credits": {
+                "cast": [
+                    {"name": "Carrie-Anne Moss", "character": "Trinity", "order": 1},
+                    {"name": "Keanu Reeves", "character": "Neo", "order": 0}
+                ],
+                "crew": [
+                    {"name": "Lilly Wachowski", "job": "Director", "department": "Directing"},
+                    {"name": "Bill Pope", "job": "Director of Photography"}
+                ]
+            }
+        }"#,
+    ).unwrap();
+    assert_eq!(details.certification("us"), Some("R"));
+    assert_eq!(details.certification("FR"), None);
+    assert_eq!(details.directors(), vec!["Lilly Wachowski"]);
+    assert_eq!(details.cast(1), vec!["Keanu Reeves"]);
+}
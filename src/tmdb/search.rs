@@ -1,10 +1,9 @@
 use std::collections::HashMap;
 
-use reqwest::{Client, StatusCode};
-
 use error;
+use tmdb::Client;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Movie {
     pub id: i64,
     pub title: String,
@@ -13,13 +12,34 @@ pub struct Movie {
     pub release_date: String,
     pub poster_path: Option<String>,
     pub backdrop_path: Option<String>,
+    #[serde(default)]
+    pub popularity: f64,
 }
 
 impl Movie {
-    pub fn year(&self) -> i32 {
-        self.release_date[..4]
-            .parse()
-            .expect("invalid year in release date")
+    pub fn year(&self) -> Option<i32> {
+        self.release_date.get(..4).and_then(|y| y.parse().ok())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Show {
+    pub id: i64,
+    pub name: String,
+    pub original_name: String,
+    pub overview: String,
+    #[serde(default)]
+    pub first_air_date: String,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+    #[serde(default)]
+    pub popularity: f64,
+}
+
+impl Show {
+    /// Year of the first air date. Some shows do not have an air date yet.
+    pub fn year(&self) -> Option<i32> {
+        self.first_air_date.get(..4).and_then(|y| y.parse().ok())
     }
 }
 
@@ -37,31 +57,31 @@ pub struct Error {
     pub status_code: i32,
 }
 
-static BASE_URL: &'static str = "https://api.themoviedb.org/3";
-static API_KEY: &'static str = include_str!("API_KEY.txt");
-
-pub fn movie(query: &str, year: Option<i32>) -> Result<Paged<Movie>, error::Error> {
-    let url = format!("{}/search/movie", BASE_URL);
-    let client = Client::new();
-
+pub fn movie(
+    client: &Client,
+    query: &str,
+    year: Option<i32>,
+) -> Result<Paged<Movie>, error::Error> {
     let mut params: HashMap<&'static str, String> = HashMap::new();
-    params.insert("api_key", API_KEY.trim().into());
     params.insert("query", query.into());
     if let Some(year) = year {
         params.insert("year", format!("{}", year));
     }
+    if let Some(ref region) = client.region {
+        params.insert("region", region.clone());
+    }
+    client.get("/search/movie", params)
+}
 
-    let req = client.get(&url).query(&params).build()?;
-    let mut resp = client.execute(req)?;
-
-    match resp.status() {
-        StatusCode::Ok => {
-            let results: Paged<Movie> = resp.json()?;
-            Ok(results)
-        }
-        _ => {
-            let error: Error = resp.json()?;
-            Err(error.into())
-        }
+pub fn tv(
+    client: &Client,
+    query: &str,
+    year: Option<i32>,
+) -> Result<Paged<Show>, error::Error> {
+    let mut params: HashMap<&'static str, String> = HashMap::new();
+    params.insert("query", query.into());
+    if let Some(year) = year {
+        params.insert("first_air_date_year", format!("{}", year));
     }
+    client.get("/search/tv", params)
 }
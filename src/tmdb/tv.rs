@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use error;
+use tmdb::{search, Client};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Details {
+    pub id: i64,
+    pub name: String,
+    pub seasons: Vec<SeasonSummary>,
+}
+
+impl Details {
+    /// Convert an absolute episode number into a season and episode number.
+    ///
+    /// Specials (season 0) are not counted in the absolute numbering.
+    pub fn resolve_absolute(&self, absolute: i32) -> Option<(i32, i32)> {
+        let mut seasons: Vec<&SeasonSummary> = self.seasons
+            .iter()
+            .filter(|s| s.season_number > 0)
+            .collect();
+        seasons.sort_by_key(|s| s.season_number);
+
+        let mut remaining = absolute;
+        for season in seasons {
+            if remaining <= season.episode_count {
+                return Some((season.season_number, remaining));
+            }
+            remaining -= season.episode_count;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SeasonSummary {
+    pub season_number: i32,
+    pub episode_count: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Season {
+    pub id: i64,
+    pub name: String,
+    pub season_number: i32,
+    #[serde(default)]
+    pub overview: String,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Episode {
+    pub id: i64,
+    pub name: String,
+    pub season_number: i32,
+    pub episode_number: i32,
+    #[serde(default)]
+    pub overview: String,
+    pub air_date: Option<String>,
+}
+
+pub fn get(client: &Client, show_id: i64) -> Result<search::Show, error::Error> {
+    client.get(&format!("/tv/{}", show_id), HashMap::new())
+}
+
+pub fn details(client: &Client, show_id: i64) -> Result<Details, error::Error> {
+    client.get(&format!("/tv/{}", show_id), HashMap::new())
+}
+
+pub fn season(client: &Client, show_id: i64, season_number: i32) -> Result<Season, error::Error> {
+    client.get(
+        &format!("/tv/{}/season/{}", show_id, season_number),
+        HashMap::new(),
+    )
+}
+
+#[test]
+fn test_resolve_absolute() {
+    let details = Details {
+        id: 1,
+        name: "show".into(),
+        seasons: vec![
+            SeasonSummary {
+                season_number: 0,
+                episode_count: 3,
+            },
+            SeasonSummary {
+                season_number: 1,
+                episode_count: 12,
+            },
+            SeasonSummary {
+                season_number: 2,
+                episode_count: 10,
+            },
+        ],
+    };
+    assert_eq!(details.resolve_absolute(5), Some((1, 5)));
+    assert_eq!(details.resolve_absolute(13), Some((2, 1)));
+    assert_eq!(details.resolve_absolute(23), None);
+}